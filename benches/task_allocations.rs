@@ -0,0 +1,49 @@
+extern crate task_kit;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use task_kit::prelude::*;
+
+const TASK_COUNT: usize = 1_000_000;
+
+struct CountingAlloc;
+
+static ALLOC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOC_COUNT.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn run_tasks(recycle_task_allocations: bool) {
+  let mut runner = Runner::builder()
+    .recycle_task_allocations(recycle_task_allocations)
+    .build();
+  let start_count = ALLOC_COUNT.load(Ordering::SeqCst);
+  let start = Instant::now();
+  for _ in 0..TASK_COUNT {
+    runner.run(Task::<(), ()>::new(|| State::Resolve(())));
+  }
+  runner.finish();
+  let duration = start.elapsed();
+  let alloc_count = ALLOC_COUNT.load(Ordering::SeqCst) - start_count;
+  println!(
+    "recycle_task_allocations({}): {} tasks, {} allocations, took {:?}",
+    recycle_task_allocations, TASK_COUNT, alloc_count, duration
+  );
+}
+
+fn main() {
+  run_tasks(false);
+  run_tasks(true);
+}
@@ -1,15 +1,26 @@
+#![cfg_attr(feature = "try_trait", feature(try_trait_v2, try_trait_v2_residual))]
+
 extern crate num_cpus;
 extern crate rand;
 
 #[cfg(feature = "futures_support")]
 extern crate futures;
+#[cfg(feature = "crossbeam_channel")]
+extern crate crossbeam_channel;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
 pub mod runner;
 pub mod task;
 
 pub mod prelude {
+  pub use runner::CancelToken;
   pub use runner::Executable;
+  pub use runner::Priority;
   pub use runner::Runner;
+  pub use runner::RunnerHandle;
+  pub use runner::TaskHandle;
   pub use task::State;
   pub use task::State::*;
   pub use task::Task;
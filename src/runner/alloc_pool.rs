@@ -0,0 +1,140 @@
+use std::alloc::{self, Layout};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+// Allocations are recycled in power of two size classes from 16 to 2048
+// bytes. Larger or more strictly aligned tasks are allocated as usual.
+const MIN_CLASS_SIZE: usize = 16;
+const CLASS_COUNT: usize = 8;
+const CLASS_ALIGN: usize = 16;
+
+// The number of free blocks kept per size class. Blocks released beyond this
+// are returned to the global allocator, bounding the memory the pool holds.
+const MAX_CLASS_LEN: usize = 1024;
+
+/// A bounded pool of allocations for recycling boxed tasks.
+///
+/// Allocations are grouped by size class. Taking a block reuses a free one
+/// of the class if there is one, and giving a block back keeps it for reuse
+/// unless its class already holds the maximum number of free blocks. The
+/// pool is shared between clones and can be used from any thread.
+#[doc(hidden)]
+#[derive(Debug, Clone, Default)]
+pub struct AllocPool(Arc<[Mutex<Vec<Block>>; CLASS_COUNT]>);
+
+impl AllocPool {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Take a block large enough for a given layout
+  ///
+  /// Returns `None` if the layout has no size class, in which case it
+  /// should be allocated without the pool.
+  pub fn take(&self, layout: Layout) -> Option<Block> {
+    let class = size_class(layout)?;
+    if let Some(block) = self.0[class].lock().unwrap().pop() {
+      return Some(block);
+    }
+    let class_layout = class_layout(class);
+    // SAFETY: Class layouts are never zero sized
+    let ptr = unsafe { alloc::alloc(class_layout) };
+    match NonNull::new(ptr) {
+      Some(ptr) => Some(Block { ptr, class }),
+      None => alloc::handle_alloc_error(class_layout),
+    }
+  }
+
+  /// Give a block back to the pool for reuse
+  pub fn give(&self, block: Block) {
+    let mut blocks = self.0[block.class].lock().unwrap();
+    if blocks.len() < MAX_CLASS_LEN {
+      blocks.push(block);
+    }
+  }
+
+  /// Get the number of free blocks held by the pool
+  pub fn free_len(&self) -> usize {
+    self.0.iter().map(|blocks| blocks.lock().unwrap().len()).sum()
+  }
+}
+
+/// An allocation from an `AllocPool`, freed when dropped.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Block {
+  ptr: NonNull<u8>,
+  class: usize,
+}
+
+// A block is uniquely owned memory holding no value of its own, so it may
+// be moved to and freed from any thread.
+unsafe impl Send for Block {}
+
+impl Block {
+  pub fn as_ptr(&self) -> *mut u8 {
+    self.ptr.as_ptr()
+  }
+}
+
+impl Drop for Block {
+  fn drop(&mut self) {
+    // SAFETY: The block was allocated with the layout of its class, and is
+    // freed only here
+    unsafe { alloc::dealloc(self.ptr.as_ptr(), class_layout(self.class)) }
+  }
+}
+
+fn size_class(layout: Layout) -> Option<usize> {
+  if layout.size() == 0 || layout.align() > CLASS_ALIGN {
+    return None;
+  }
+  let size = layout.size().max(MIN_CLASS_SIZE).next_power_of_two();
+  let class = (size.trailing_zeros() - MIN_CLASS_SIZE.trailing_zeros()) as usize;
+  if class < CLASS_COUNT {
+    Some(class)
+  } else {
+    None
+  }
+}
+
+fn class_layout(class: usize) -> Layout {
+  Layout::from_size_align(MIN_CLASS_SIZE << class, CLASS_ALIGN).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reuses_blocks_of_the_same_size_class() {
+    let alloc_pool = AllocPool::new();
+    let block = alloc_pool.take(Layout::new::<[u64; 3]>()).unwrap();
+    let ptr = block.as_ptr();
+    alloc_pool.give(block);
+    assert_eq!(alloc_pool.free_len(), 1);
+
+    let block = alloc_pool.take(Layout::new::<[u64; 4]>()).unwrap();
+    assert_eq!(block.as_ptr(), ptr);
+    assert_eq!(alloc_pool.free_len(), 0);
+  }
+
+  #[test]
+  fn bounds_the_number_of_free_blocks() {
+    let alloc_pool = AllocPool::new();
+    let blocks: Vec<_> = (0..MAX_CLASS_LEN + 10)
+      .map(|_| alloc_pool.take(Layout::new::<u64>()).unwrap())
+      .collect();
+    for block in blocks {
+      alloc_pool.give(block);
+    }
+    assert_eq!(alloc_pool.free_len(), MAX_CLASS_LEN);
+  }
+
+  #[test]
+  fn has_no_size_class_for_large_or_zero_sized_layouts() {
+    let alloc_pool = AllocPool::new();
+    assert!(alloc_pool.take(Layout::new::<[u8; 4096]>()).is_none());
+    assert!(alloc_pool.take(Layout::new::<()>()).is_none());
+  }
+}
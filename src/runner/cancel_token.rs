@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::Executable;
+
+/// A token for cancelling a task after it has been given to the runner.
+///
+/// Tokens can be cloned and shared between threads. Once cancelled, the task
+/// is dropped by the runner the next time it would be executed.
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Cancel the task associated with the token
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+
+  /// Check if the token has been cancelled
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Cancellable<T> {
+  task: T,
+  token: CancelToken,
+}
+
+impl<T> Cancellable<T>
+where
+  T: Executable,
+{
+  pub fn new(task: T, token: CancelToken) -> Self {
+    Self { task, token }
+  }
+}
+
+impl<T> Executable for Cancellable<T>
+where
+  T: Executable,
+{
+  fn exec(&mut self) -> bool {
+    self.token.is_cancelled() || self.task.exec()
+  }
+
+  fn name(&self) -> Option<&str> {
+    self.task.name()
+  }
+
+  fn label(&self) -> Option<&'static str> {
+    self.task.label()
+  }
+
+  fn estimated_cost(&self) -> usize {
+    self.task.estimated_cost()
+  }
+}
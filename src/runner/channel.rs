@@ -0,0 +1,12 @@
+//! The channel types used to deliver task results.
+//!
+//! By default these are the channels of `std::sync::mpsc`. With the
+//! `crossbeam_channel` feature enabled they are the channels of the
+//! `crossbeam-channel` crate instead, allowing the receivers returned by
+//! the runner to be used with crossbeam's `select!`.
+
+#[cfg(not(feature = "crossbeam_channel"))]
+pub use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+
+#[cfg(feature = "crossbeam_channel")]
+pub use crossbeam_channel::{unbounded as channel, Receiver, Sender, TryRecvError};
@@ -1,14 +1,59 @@
 use std::fmt::Debug;
-use std::marker::{Send, Sync};
+use std::marker::Send;
 
 /// Allows an implementer to be executed by the runner
 ///
 /// Implement this trait if you wish to pass a custom type to the runner
 /// for execution.
-pub trait Executable: Send + Sync + Debug {
+pub trait Executable: Send + Debug {
   /// Execute the task or other custom type
   ///
   /// Exec is called by the runner repeatedly until it returns true.
   /// Returning true indicates the the task is complete.
   fn exec(&mut self) -> bool;
+
+  /// Get the name of the task or other custom type
+  ///
+  /// The name is passed to the lifecycle callbacks registered on the runner.
+  /// By default executables are unnamed.
+  fn name(&self) -> Option<&str> {
+    None
+  }
+
+  /// Get the label of the task or other custom type
+  ///
+  /// Labeled executables are counted within `Runner::label_stats`. By
+  /// default executables are unlabeled.
+  fn label(&self) -> Option<&'static str> {
+    None
+  }
+
+  /// Get the estimated cost of executing the task or other custom type
+  ///
+  /// The cost is used by `Runner::run_all_weighted` to balance work across
+  /// the runner's queues. By default executables have a cost of one.
+  fn estimated_cost(&self) -> usize {
+    1
+  }
+}
+
+impl<T> Executable for Box<T>
+where
+  T: Executable + ?Sized,
+{
+  fn exec(&mut self) -> bool {
+    (**self).exec()
+  }
+
+  fn name(&self) -> Option<&str> {
+    (**self).name()
+  }
+
+  fn label(&self) -> Option<&'static str> {
+    (**self).label()
+  }
+
+  fn estimated_cost(&self) -> usize {
+    (**self).estimated_cost()
+  }
 }
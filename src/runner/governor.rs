@@ -0,0 +1,61 @@
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use super::worker::{self, Worker};
+use super::TaskQueueSet;
+
+const SAMPLE_INTERVAL_MS: u64 = 10;
+
+/// Scales the number of workers in a runner according to load.
+///
+/// The governor periodically samples the number of queued tasks. While there
+/// are more queued tasks than workers it adds a worker, up to `max`. Once no
+/// tasks are queued or executing it removes a worker, down to `min`.
+#[derive(Debug)]
+pub struct Governor {
+  is_running: Arc<Mutex<bool>>,
+  inner_handle: JoinHandle<()>,
+}
+
+impl Governor {
+  pub fn new(
+    task_queue_set: TaskQueueSet,
+    workers: Arc<Mutex<Vec<Worker>>>,
+    min: usize,
+    max: usize,
+  ) -> Self {
+    let is_running = Arc::new(Mutex::new(true));
+    let governor_is_running = is_running.clone();
+    let inner_handle = thread::spawn(move || {
+      while *governor_is_running.lock().unwrap() {
+        thread::sleep(Duration::from_millis(SAMPLE_INTERVAL_MS));
+
+        let removed = {
+          let mut workers = workers.lock().unwrap();
+          let worker_count = workers.len();
+          let queued_count = task_queue_set.len();
+          let outstanding_count = task_queue_set.outstanding_len();
+          if queued_count > worker_count && worker_count < max {
+            worker::resize(&mut workers, &task_queue_set, worker_count + 1)
+          } else if outstanding_count == 0 && worker_count > min {
+            worker::resize(&mut workers, &task_queue_set, worker_count - 1)
+          } else {
+            Vec::new()
+          }
+        };
+        for worker in removed {
+          worker.join();
+        }
+      }
+    });
+    Self {
+      is_running,
+      inner_handle,
+    }
+  }
+
+  pub fn finish(self) {
+    *self.is_running.lock().unwrap() = false;
+    self.inner_handle.join().unwrap();
+  }
+}
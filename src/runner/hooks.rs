@@ -0,0 +1,77 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::time::Duration;
+
+type StartHook = Arc<dyn Fn(Option<&str>) + Send + Sync>;
+type SettleHook = Arc<dyn Fn(Option<&str>, Duration) + Send + Sync>;
+type DeadlockHook = Arc<dyn Fn(&[String]) + Send + Sync>;
+
+/// Callbacks invoked by the workers as tasks move through their lifecycle.
+///
+/// Each callback is given the name of the task, if it has one. Completion
+/// and panic callbacks are also given the time elapsed since the task was
+/// first executed.
+#[doc(hidden)]
+#[derive(Default, Clone)]
+pub struct Hooks {
+  on_task_start: Vec<StartHook>,
+  on_task_complete: Vec<SettleHook>,
+  on_task_panic: Vec<SettleHook>,
+  on_deadlock_suspected: Vec<DeadlockHook>,
+}
+
+impl Hooks {
+  pub fn add_task_start(&mut self, hook: StartHook) {
+    self.on_task_start.push(hook);
+  }
+
+  pub fn add_task_complete(&mut self, hook: SettleHook) {
+    self.on_task_complete.push(hook);
+  }
+
+  pub fn add_task_panic(&mut self, hook: SettleHook) {
+    self.on_task_panic.push(hook);
+  }
+
+  pub fn add_deadlock_suspected(&mut self, hook: DeadlockHook) {
+    self.on_deadlock_suspected.push(hook);
+  }
+
+  pub fn task_start(&self, name: Option<&str>) {
+    for hook in &self.on_task_start {
+      hook(name);
+    }
+  }
+
+  pub fn task_complete(&self, name: Option<&str>, elapsed: Duration) {
+    for hook in &self.on_task_complete {
+      hook(name, elapsed);
+    }
+  }
+
+  pub fn task_panic(&self, name: Option<&str>, elapsed: Duration) {
+    for hook in &self.on_task_panic {
+      hook(name, elapsed);
+    }
+  }
+
+  pub fn deadlock_suspected(&self, names: &[String]) {
+    for hook in &self.on_deadlock_suspected {
+      hook(names);
+    }
+  }
+}
+
+impl Debug for Hooks {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "Hooks {{ on_task_start: {}, on_task_complete: {}, on_task_panic: {}, \
+       on_deadlock_suspected: {} }}",
+      self.on_task_start.len(),
+      self.on_task_complete.len(),
+      self.on_task_panic.len(),
+      self.on_deadlock_suspected.len()
+    )
+  }
+}
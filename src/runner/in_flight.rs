@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+const UNNAMED: &str = "<unnamed>";
+
+/// Tracks the tasks that have started but not yet settled.
+///
+/// Used by the watchdog to report the tasks that are in flight when the
+/// runner stops making progress.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct InFlight {
+  counts: HashMap<String, usize>,
+  last_settled_at: Instant,
+}
+
+impl InFlight {
+  pub fn new() -> Self {
+    Self {
+      counts: HashMap::new(),
+      last_settled_at: Instant::now(),
+    }
+  }
+
+  pub fn start(&mut self, name: Option<&str>) {
+    *self
+      .counts
+      .entry(name.unwrap_or(UNNAMED).to_string())
+      .or_insert(0) += 1;
+  }
+
+  pub fn settle(&mut self, name: Option<&str>) {
+    let name = name.unwrap_or(UNNAMED);
+    let remove = match self.counts.get_mut(name) {
+      Some(count) => {
+        *count -= 1;
+        *count == 0
+      }
+      None => false,
+    };
+    if remove {
+      self.counts.remove(name);
+    }
+    self.last_settled_at = Instant::now();
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    let mut names: Vec<_> = self.counts.keys().cloned().collect();
+    names.sort();
+    names
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.counts.is_empty()
+  }
+
+  pub fn last_settled_at(&self) -> Instant {
+    self.last_settled_at
+  }
+}
+
+impl Default for InFlight {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,26 @@
+use std::time::Instant;
+use super::Priority;
+use super::TaskBox;
+
+/// A task queued within the runner, along with its bookkeeping.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Job {
+  pub task: TaskBox,
+  pub started_at: Option<Instant>,
+  pub priority: Priority,
+}
+
+impl Job {
+  pub fn new(task: TaskBox) -> Self {
+    Self::with_priority(task, Priority::Normal)
+  }
+
+  pub fn with_priority(task: TaskBox, priority: Priority) -> Self {
+    Self {
+      task,
+      started_at: None,
+      priority,
+    }
+  }
+}
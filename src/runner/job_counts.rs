@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts of the jobs within a set of queues, shared by each queue.
+///
+/// A job is outstanding from when it is inserted into a queue until it
+/// settles or is cleared, including while it is being executed or moved
+/// between queues. Taking a job out of a queue to execute it marks it as
+/// executing while the queue is still locked, so that a job being executed
+/// is never missing from both counts.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct JobCounts {
+  outstanding: AtomicUsize,
+  executing: AtomicUsize,
+}
+
+impl JobCounts {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert(&self) {
+    self.outstanding.fetch_add(1, Ordering::SeqCst);
+  }
+
+  pub fn clear(&self, n: usize) {
+    self.outstanding.fetch_sub(n, Ordering::SeqCst);
+  }
+
+  pub fn start_executing(&self) {
+    self.executing.fetch_add(1, Ordering::SeqCst);
+  }
+
+  pub fn finish_executing(&self) {
+    self.executing.fetch_sub(1, Ordering::SeqCst);
+  }
+
+  /// Mark an executing job as settled, after which it is no longer
+  /// outstanding
+  pub fn settle(&self) {
+    self.executing.fetch_sub(1, Ordering::SeqCst);
+    self.outstanding.fetch_sub(1, Ordering::SeqCst);
+  }
+
+  pub fn outstanding_len(&self) -> usize {
+    self.outstanding.load(Ordering::SeqCst)
+  }
+
+  pub fn executing_len(&self) -> usize {
+    self.executing.load(Ordering::SeqCst)
+  }
+}
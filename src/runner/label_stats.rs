@@ -0,0 +1,10 @@
+/// Execution counts for the tasks given a particular label.
+///
+/// See `Task::labeled` and `Runner::label_stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LabelStats {
+  /// The number of labeled tasks that have completed
+  pub executed: usize,
+  /// The number of labeled tasks that have started but not yet settled
+  pub in_flight: usize,
+}
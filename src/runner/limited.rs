@@ -0,0 +1,54 @@
+use super::Executable;
+use super::super::task::{Permit, Semaphore};
+
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Limited<T> {
+  task: T,
+  semaphore: Semaphore,
+  permit: Option<Permit>,
+}
+
+impl<T> Limited<T>
+where
+  T: Executable,
+{
+  pub fn new(task: T, semaphore: Semaphore) -> Self {
+    Self {
+      task,
+      semaphore,
+      permit: None,
+    }
+  }
+}
+
+impl<T> Executable for Limited<T>
+where
+  T: Executable,
+{
+  fn exec(&mut self) -> bool {
+    if self.permit.is_none() {
+      self.permit = self.semaphore.try_acquire();
+      if self.permit.is_none() {
+        return false;
+      }
+    }
+    if !self.task.exec() {
+      return false;
+    }
+    self.permit = None;
+    true
+  }
+
+  fn name(&self) -> Option<&str> {
+    self.task.name()
+  }
+
+  fn label(&self) -> Option<&'static str> {
+    self.task.label()
+  }
+
+  fn estimated_cost(&self) -> usize {
+    self.task.estimated_cost()
+  }
+}
@@ -0,0 +1,36 @@
+use std::fmt::{self, Debug};
+use super::Executable;
+
+/// An executable wrapping a closure that returns true once complete.
+///
+/// See `Runner::run_loop`.
+#[doc(hidden)]
+pub struct LoopTask<F>(F);
+
+impl<F> LoopTask<F>
+where
+  F: FnMut() -> bool + Send,
+{
+  pub fn new(f: F) -> Self {
+    LoopTask(f)
+  }
+
+  pub fn into_inner(self) -> F {
+    self.0
+  }
+}
+
+impl<F> Debug for LoopTask<F> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "LoopTask")
+  }
+}
+
+impl<F> Executable for LoopTask<F>
+where
+  F: FnMut() -> bool + Send,
+{
+  fn exec(&mut self) -> bool {
+    (self.0)()
+  }
+}
@@ -1,11 +1,48 @@
+mod alloc_pool;
+mod cancel_token;
+pub mod channel;
 mod executable;
+mod governor;
+mod hooks;
+mod in_flight;
+mod job;
+mod job_counts;
+mod label_stats;
+mod limited;
+mod loop_task;
+mod pipeline;
+mod priority;
 mod runner;
+mod runner_builder;
+mod runner_handle;
+mod submit_error;
+mod task_box;
+mod task_handle;
 mod task_queue_set;
 mod task_queue;
+mod watchdog;
 mod worker;
 
+pub use self::alloc_pool::{AllocPool, Block};
+pub use self::cancel_token::{CancelToken, Cancellable};
 pub use self::executable::Executable;
+pub use self::governor::Governor;
+pub use self::hooks::Hooks;
+pub use self::in_flight::InFlight;
+pub use self::job::Job;
+pub use self::job_counts::JobCounts;
+pub use self::label_stats::LabelStats;
+pub use self::limited::Limited;
+pub use self::loop_task::LoopTask;
+pub use self::pipeline::Pipeline;
+pub use self::priority::Priority;
 pub use self::runner::Runner;
+pub use self::runner_builder::RunnerBuilder;
+pub use self::runner_handle::RunnerHandle;
+pub use self::submit_error::SubmitError;
+pub use self::task_box::TaskBox;
+pub use self::task_handle::{HandleSender, TaskHandle};
 pub use self::task_queue_set::TaskQueueSet;
 pub use self::task_queue::TaskQueue;
+pub use self::watchdog::Watchdog;
 pub use self::worker::{Worker, WorkerInner};
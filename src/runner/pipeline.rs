@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError, TrySendError};
+use super::HandleSender;
+use super::TaskHandle;
+use super::super::task::{State, Task};
+
+const DEFAULT_BUFFER_LEN: usize = 16;
+
+// An item tagged with its position within the batch, so that its result can
+// be delivered to the matching handle once it leaves the final stage
+type Item<T, E> = (usize, Result<T, E>);
+
+// The tasks that run a batch through a pipeline, and the handles to the
+// result of each item
+type BatchTasks<O, E> = (Vec<Task<'static, (), ()>>, Vec<TaskHandle<O, E>>);
+
+type StageFn<I, O, E> = dyn Fn(I) -> Task<'static, O, E> + Send + Sync;
+
+type Connect<I, O, E> =
+  dyn Fn(Receiver<Item<I, E>>, usize, &mut Vec<Task<'static, (), ()>>) -> Receiver<Item<O, E>>
+    + Send
+    + Sync;
+
+/// A fixed sequence of stages that items are processed through.
+///
+/// Each stage is a closure that takes the output of the previous stage and
+/// returns a task producing the input for the next. When run with
+/// `Runner::run_pipeline`, every stage becomes its own task within the
+/// thread pool, and the stages are connected by bounded channels. A stage
+/// works on no more than the buffer length of items at a time, and stops
+/// taking new items while the channel to the next stage is full, so a slow
+/// stage holds back the stages before it rather than letting items pile up.
+/// A rejection at any stage skips the remaining stages.
+///
+/// # Examples
+///
+/// ```
+/// # use task_kit::prelude::*;
+/// # use task_kit::runner::Pipeline;
+/// let mut runner = Runner::new();
+/// let pipeline = Pipeline::new(|s: &str| Task::from(s.len()))
+///   .stage(|n: usize| Task::<_, ()>::from(n * 2));
+/// let handles = runner.run_pipeline(&pipeline, vec!["a", "bb"]);
+/// # runner.finish();
+/// ```
+pub struct Pipeline<I, O, E> {
+  buffer_len: usize,
+  connect: Arc<Connect<I, O, E>>,
+}
+
+impl<I, O, E> Pipeline<I, O, E>
+where
+  I: Send + 'static,
+  O: Send + 'static,
+  E: Send + 'static,
+{
+  /// Create a new pipeline from its first stage
+  pub fn new<F>(stage: F) -> Self
+  where
+    F: Fn(I) -> Task<'static, O, E> + Send + Sync + 'static,
+  {
+    let stage = Arc::new(stage);
+    Self {
+      buffer_len: DEFAULT_BUFFER_LEN,
+      connect: Arc::new(move |input, buffer_len, tasks| {
+        let (sender, receiver) = sync_channel(buffer_len);
+        tasks.push(Stage::task(stage.clone(), input, sender, buffer_len));
+        receiver
+      }),
+    }
+  }
+
+  /// Append a stage to the pipeline
+  ///
+  /// The stage is given the resolved output of the previous stage.
+  pub fn stage<F, U>(self, stage: F) -> Pipeline<I, U, E>
+  where
+    F: Fn(O) -> Task<'static, U, E> + Send + Sync + 'static,
+    U: Send + 'static,
+  {
+    let connect = self.connect;
+    let stage = Arc::new(stage);
+    Pipeline {
+      buffer_len: self.buffer_len,
+      connect: Arc::new(move |input, buffer_len, tasks| {
+        let input = connect(input, buffer_len, tasks);
+        let (sender, receiver) = sync_channel(buffer_len);
+        tasks.push(Stage::task(stage.clone(), input, sender, buffer_len));
+        receiver
+      }),
+    }
+  }
+
+  /// Set the number of items buffered between stages
+  ///
+  /// This is both the capacity of the channel between each pair of stages,
+  /// and the number of items each stage works on at a time. Defaults to 16.
+  /// A length of zero is treated as one.
+  pub fn buffer_len(mut self, n: usize) -> Self {
+    self.buffer_len = n.max(1);
+    self
+  }
+
+  /// Create the tasks that stream a batch of items through the pipeline
+  ///
+  /// Returns a task feeding the items into the first stage, a task for each
+  /// stage, and a task delivering the results from the final stage, along
+  /// with a handle to the result of each item in the order given.
+  #[doc(hidden)]
+  pub fn tasks(&self, items: Vec<I>) -> BatchTasks<O, E> {
+    let (mut senders, handles): (Vec<Option<HandleSender<_, _>>>, Vec<_>) = items
+      .iter()
+      .map(|_| {
+        let (sender, handle) = TaskHandle::channel();
+        (Some(sender), handle)
+      })
+      .unzip();
+
+    let (input_sender, input) = sync_channel(self.buffer_len);
+    let mut items: VecDeque<_> = items.into_iter().map(Ok).enumerate().collect();
+    let mut tasks = vec![Task::new(move || {
+      while let Some(item) = items.pop_front() {
+        match input_sender.try_send(item) {
+          Ok(()) => {}
+          Err(TrySendError::Full(item)) => {
+            items.push_front(item);
+            return State::Pending;
+          }
+          Err(TrySendError::Disconnected(_)) => break,
+        }
+      }
+      State::Resolve(())
+    })];
+
+    let output = (self.connect)(input, self.buffer_len, &mut tasks);
+
+    tasks.push(Task::new(move || loop {
+      match output.try_recv() {
+        Ok((index, result)) => {
+          if let Some(sender) = senders[index].take() {
+            sender.send(result);
+          }
+        }
+        Err(TryRecvError::Empty) => return State::Pending,
+        Err(TryRecvError::Disconnected) => return State::Resolve(()),
+      }
+    }));
+
+    (tasks, handles)
+  }
+}
+
+impl<I, O, E> Debug for Pipeline<I, O, E> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Pipeline")
+  }
+}
+
+// A stage of a running pipeline, executed as a single task within the pool
+struct Stage<I, O, E> {
+  stage: Arc<StageFn<I, O, E>>,
+  input: Receiver<Item<I, E>>,
+  output: SyncSender<Item<O, E>>,
+  buffer_len: usize,
+  is_drained: bool,
+  in_progress: Vec<(usize, Task<'static, O, E>)>,
+  settled: VecDeque<Item<O, E>>,
+}
+
+impl<I, O, E> Stage<I, O, E>
+where
+  I: Send + 'static,
+  O: Send + 'static,
+  E: Send + 'static,
+{
+  fn task(
+    stage: Arc<StageFn<I, O, E>>,
+    input: Receiver<Item<I, E>>,
+    output: SyncSender<Item<O, E>>,
+    buffer_len: usize,
+  ) -> Task<'static, (), ()> {
+    let mut stage = Stage {
+      stage,
+      input,
+      output,
+      buffer_len,
+      is_drained: false,
+      in_progress: Vec::new(),
+      settled: VecDeque::new(),
+    };
+    Task::new(move || stage.poll())
+  }
+
+  fn poll(&mut self) -> State<(), ()> {
+    let settled = &mut self.settled;
+    self.in_progress.retain_mut(|(index, task)| match task.poll() {
+      Some(result) => {
+        settled.push_back((*index, result));
+        false
+      }
+      None => true,
+    });
+
+    while let Some(item) = self.settled.pop_front() {
+      match self.output.try_send(item) {
+        Ok(()) => {}
+        Err(TrySendError::Full(item)) => {
+          self.settled.push_front(item);
+          break;
+        }
+        // The next stage is gone, so there is nowhere left to send items
+        Err(TrySendError::Disconnected(_)) => return State::Resolve(()),
+      }
+    }
+
+    while !self.is_drained && self.in_progress.len() + self.settled.len() < self.buffer_len {
+      match self.input.try_recv() {
+        Ok((index, Ok(value))) => self.in_progress.push((index, (self.stage)(value))),
+        Ok((index, Err(e))) => self.settled.push_back((index, Err(e))),
+        Err(TryRecvError::Empty) => break,
+        Err(TryRecvError::Disconnected) => self.is_drained = true,
+      }
+    }
+
+    if self.is_drained && self.in_progress.is_empty() && self.settled.is_empty() {
+      State::Resolve(())
+    } else {
+      State::Pending
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+  use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+  use std::thread;
+  use std::time::Duration;
+  use super::*;
+  use super::super::Runner;
+
+  #[test]
+  fn can_run_items_through_pipeline() {
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let stage_sink = sink.clone();
+    let pipeline = Pipeline::new(|s: &'static str| Task::from(s.parse::<i32>()))
+      .stage(|r| match r {
+        Ok(n) => Task::from(n * 10),
+        Err(_) => Task::new(|| State::Reject("not a number")),
+      })
+      .stage(move |n| {
+        stage_sink.lock().unwrap().push(n);
+        Task::from(n)
+      });
+
+    let mut runner = Runner::with_worker_count(2);
+    let results: Vec<_> = runner
+      .run_pipeline(&pipeline, vec!["1", "2", "x", "3"])
+      .into_iter()
+      .map(|handle| handle.wait().unwrap())
+      .collect();
+    runner.finish();
+
+    assert_eq!(results, vec![Ok(10), Ok(20), Err("not a number"), Ok(30)]);
+    let mut sunk = sink.lock().unwrap().clone();
+    sunk.sort();
+    assert_eq!(sunk, vec![10, 20, 30]);
+  }
+
+  #[test]
+  fn buffers_a_bounded_number_of_items_between_stages() {
+    let started_count = Arc::new(AtomicUsize::new(0));
+    let is_open = Arc::new(AtomicBool::new(false));
+    let stage_started_count = started_count.clone();
+    let stage_is_open = is_open.clone();
+    let pipeline = Pipeline::new(move |n: usize| {
+      stage_started_count.fetch_add(1, Ordering::SeqCst);
+      Task::<_, ()>::from(n)
+    })
+    .stage(move |n| {
+      let is_open = stage_is_open.clone();
+      Task::new(move || match is_open.load(Ordering::SeqCst) {
+        true => State::Resolve(n * 2),
+        false => State::Pending,
+      })
+    })
+    .buffer_len(2);
+
+    let mut runner = Runner::with_worker_count(4);
+    let handles = runner.run_pipeline(&pipeline, (0..100).collect());
+    thread::sleep(Duration::from_millis(50));
+
+    // At most two items in progress in each stage and two in the channel
+    // between them, while the second stage is held closed
+    assert!(started_count.load(Ordering::SeqCst) <= 6);
+
+    is_open.store(true, Ordering::SeqCst);
+    let results: Vec<_> = handles.into_iter().map(|handle| handle.wait().unwrap()).collect();
+    runner.finish();
+
+    assert_eq!(results, (0..100).map(|n| Ok(n * 2)).collect::<Vec<_>>());
+    assert_eq!(started_count.load(Ordering::SeqCst), 100);
+  }
+}
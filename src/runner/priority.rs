@@ -0,0 +1,16 @@
+/// The priority of a task run upon the runner.
+///
+/// Each worker executes the tasks within its queue in priority order, so
+/// higher priority tasks run ahead of lower priority ones, with tasks of the
+/// same priority taking turns. Note that a pending task is requeued at its
+/// own priority, so a high priority task that stays pending for a long time
+/// will hold back the lower priority tasks sharing its queue.
+///
+/// See `Runner::run_with_priority`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Priority {
+  High,
+  #[default]
+  Normal,
+  Low,
+}
@@ -1,7 +1,24 @@
-use num_cpus;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use super::worker;
+use super::{CancelToken, Cancellable};
+use super::channel::{channel, Receiver, Sender};
 use super::Executable;
+use super::Governor;
+use super::LabelStats;
+use super::Limited;
+use super::Pipeline;
+use super::Priority;
+use super::RunnerBuilder;
+use super::RunnerHandle;
+use super::TaskHandle;
 use super::TaskQueueSet;
+use super::Watchdog;
 use super::Worker;
+use super::super::task::{Semaphore, State, Task};
 
 
 /// A thread pool for executing tasks.
@@ -15,25 +32,177 @@ use super::Worker;
 #[derive(Debug)]
 pub struct Runner {
   task_queue_set: TaskQueueSet,
-  workers: Vec<Worker>,
+  workers: Arc<Mutex<Vec<Worker>>>,
+  governor: Option<Governor>,
+  watchdog: Option<Watchdog>,
 }
 
 impl Runner {
   // Create a new task runner
   pub fn new() -> Self {
-    Self::with_worker_count(num_cpus::get() + 1)
+    Self::builder().build()
   }
 
   pub fn with_worker_count(n: usize) -> Self {
-    let task_queue_set = TaskQueueSet::new();
-    let workers = (0..n)
+    Self::builder().worker_count(n).build()
+  }
+
+  /// Get a builder for configuring a new runner
+  pub fn builder() -> RunnerBuilder {
+    RunnerBuilder::new()
+  }
+
+  #[doc(hidden)]
+  pub fn from_builder(builder: RunnerBuilder) -> Self {
+    let mut task_queue_set = TaskQueueSet::with_capacity(builder.queue_capacity)
+      .with_min_steal_len(builder.min_steal_len)
+      .with_thread_config(builder.thread_name_prefix, builder.stack_size);
+    if builder.deadlock_detection.is_some() {
+      task_queue_set = task_queue_set.with_in_flight_tracking();
+    }
+    if builder.recycle_task_allocations {
+      task_queue_set = task_queue_set.with_recycled_task_allocations();
+    }
+    let worker_count = match builder.autoscale {
+      Some((min, max)) => builder.worker_count.max(min).min(max),
+      None => builder.worker_count,
+    };
+    let workers = (0..worker_count)
       .map(|_| Worker::new(task_queue_set.clone()))
       .collect();
+    let workers = Arc::new(Mutex::new(workers));
+    let governor = builder
+      .autoscale
+      .map(|(min, max)| Governor::new(task_queue_set.clone(), workers.clone(), min, max));
+    let watchdog = builder
+      .deadlock_detection
+      .map(|timeout| Watchdog::new(task_queue_set.clone(), timeout));
 
     Self {
       task_queue_set,
       workers,
+      governor,
+      watchdog,
+    }
+  }
+
+  /// Grow or shrink the number of workers
+  ///
+  /// Removed workers finish the tasks already within their queues before
+  /// exiting. If the runner autoscales, the worker count will continue to be
+  /// adjusted according to load.
+  pub fn resize(&mut self, n: usize) {
+    let removed = worker::resize(&mut self.workers.lock().unwrap(), &self.task_queue_set, n);
+    for worker in removed {
+      worker.join();
+    }
+  }
+
+  /// Get the number of workers currently within the runner
+  ///
+  /// The count reflects any calls to `resize`, as well as adjustments made
+  /// if the runner autoscales.
+  pub fn worker_count(&self) -> usize {
+    self.workers.lock().unwrap().len()
+  }
+
+  /// Get a cloneable handle for submitting tasks to the runner
+  ///
+  /// Handles can be shared between threads, allowing tasks to be submitted
+  /// concurrently without moving the runner.
+  pub fn handle(&self) -> RunnerHandle {
+    RunnerHandle::new(self.task_queue_set.clone())
+  }
+
+  /// Register a callback invoked when a worker first executes a task
+  ///
+  /// The callback is given the name of the task, if it has one.
+  pub fn on_task_start<F>(&mut self, hook: F)
+  where
+    F: Fn(Option<&str>) + Send + Sync + 'static,
+  {
+    self.task_queue_set.hooks().write().unwrap().add_task_start(Arc::new(hook));
+  }
+
+  /// Register a callback invoked when a task completes
+  ///
+  /// The callback is given the name of the task, if it has one, and the time
+  /// elapsed since the task was first executed.
+  pub fn on_task_complete<F>(&mut self, hook: F)
+  where
+    F: Fn(Option<&str>, Duration) + Send + Sync + 'static,
+  {
+    self.task_queue_set.hooks().write().unwrap().add_task_complete(Arc::new(hook));
+  }
+
+  /// Register a callback invoked when a task panics
+  ///
+  /// The callback is given the name of the task, if it has one, and the time
+  /// elapsed since the task was first executed. The panicking task is dropped
+  /// and its worker carries on executing other tasks.
+  pub fn on_task_panic<F>(&mut self, hook: F)
+  where
+    F: Fn(Option<&str>, Duration) + Send + Sync + 'static,
+  {
+    self.task_queue_set.hooks().write().unwrap().add_task_panic(Arc::new(hook));
+  }
+
+  /// Get the execution counts of labeled tasks, keyed by label
+  ///
+  /// Only tasks with a label, such as those created with `Task::labeled`,
+  /// are counted.
+  pub fn label_stats(&self) -> HashMap<&'static str, LabelStats> {
+    self.task_queue_set.label_stats().lock().unwrap().clone()
+  }
+
+  /// Render the runner's metrics in the Prometheus text exposition format
+  ///
+  /// Includes the worker count, the depth of each queue, and the execution
+  /// counts reported by `label_stats`, ready to be served to a scraper.
+  #[cfg(feature = "prometheus")]
+  pub fn metrics_text(&self) -> String {
+    let mut text = String::new();
+    text.push_str("# HELP task_kit_workers Number of worker threads.\n");
+    text.push_str("# TYPE task_kit_workers gauge\n");
+    text.push_str(&format!("task_kit_workers {}\n", self.worker_count()));
+
+    text.push_str("# HELP task_kit_queue_depth Number of tasks waiting in a queue.\n");
+    text.push_str("# TYPE task_kit_queue_depth gauge\n");
+    for (queue, depth) in self.queue_depths().iter().enumerate() {
+      text.push_str(&format!("task_kit_queue_depth{{queue=\"{}\"}} {}\n", queue, depth));
     }
+
+    let mut label_stats: Vec<_> = self.label_stats().into_iter().collect();
+    label_stats.sort_by_key(|&(label, _)| label);
+    text.push_str("# HELP task_kit_tasks_executed_total Number of labeled tasks executed.\n");
+    text.push_str("# TYPE task_kit_tasks_executed_total counter\n");
+    for &(label, ref stats) in &label_stats {
+      text.push_str(&format!(
+        "task_kit_tasks_executed_total{{label=\"{}\"}} {}\n",
+        label, stats.executed
+      ));
+    }
+    text.push_str("# HELP task_kit_tasks_in_flight Number of labeled tasks in flight.\n");
+    text.push_str("# TYPE task_kit_tasks_in_flight gauge\n");
+    for &(label, ref stats) in &label_stats {
+      text.push_str(&format!(
+        "task_kit_tasks_in_flight{{label=\"{}\"}} {}\n",
+        label, stats.in_flight
+      ));
+    }
+    text
+  }
+
+  /// Register a callback invoked when a deadlock is suspected
+  ///
+  /// The callback is given the names of the tasks in flight. Callbacks are
+  /// only invoked if deadlock detection was enabled with
+  /// `RunnerBuilder::deadlock_detection`.
+  pub fn on_deadlock_suspected<F>(&mut self, hook: F)
+  where
+    F: Fn(&[String]) + Send + Sync + 'static,
+  {
+    self.task_queue_set.hooks().write().unwrap().add_deadlock_suspected(Arc::new(hook));
   }
 
   // run a task
@@ -41,22 +210,1395 @@ impl Runner {
   where
     T: Executable + 'static,
   {
-    self.task_queue_set.push_to_rand_queue(Box::new(task));
+    accepted(self.task_queue_set.push_to_rand_queue(task));
   }
 
-  // run a task
-  pub fn run_all<T>(&mut self, tasks: Vec<T>)
+  /// Run a task at a given priority
+  ///
+  /// Workers execute higher priority tasks ahead of lower priority ones
+  /// within their queues. Tasks given to `run` have `Priority::Normal`.
+  pub fn run_with_priority<T>(&mut self, task: T, priority: Priority)
   where
     T: Executable + 'static,
   {
+    accepted(self.task_queue_set.push_to_rand_queue_with_priority(task, priority));
+  }
+
+  /// Run a closure repeatedly until it returns true
+  ///
+  /// This is the simplest way to run side effects upon the runner, without
+  /// the need for a task or state.
+  pub fn run_loop<F>(&mut self, f: F)
+  where
+    F: FnMut() -> bool + Send + 'static,
+  {
+    accepted(self.handle().run_loop(f));
+  }
+
+  /// Run a task, returning a token that can be used to cancel it
+  ///
+  /// Once the token is cancelled, the task is dropped the next time it would
+  /// be executed.
+  pub fn run_cancellable<T>(&mut self, task: T) -> CancelToken
+  where
+    T: Executable + 'static,
+  {
+    let token = CancelToken::new();
+    self.run(Cancellable::new(task, token.clone()));
+    token
+  }
+
+  /// Run a task that may only execute while holding a permit from the given
+  /// semaphore
+  ///
+  /// Until a permit is available the task remains queued, yielding the
+  /// worker to other tasks. The permit is released once the task settles.
+  /// Tasks run with clones of the same semaphore share one limit, even
+  /// across separate batches.
+  pub fn run_limited<T>(&mut self, semaphore: &Semaphore, task: T)
+  where
+    T: Executable + 'static,
+  {
+    self.run(Limited::new(task, semaphore.clone()));
+  }
+
+  /// Run a task if there is room for it within the runner's queues
+  ///
+  /// If every queue is at the capacity configured with
+  /// `RunnerBuilder::queue_capacity`, the task is handed back.
+  pub fn try_run<T>(&mut self, task: T) -> Result<(), T>
+  where
+    T: Executable + 'static,
+  {
+    self.handle().try_run(task)
+  }
+
+  /// Run as many tasks as there is room for within the runner's queues
+  ///
+  /// Returns the tasks that did not fit.
+  pub fn try_run_all<T>(&mut self, tasks: Vec<T>) -> Vec<T>
+  where
+    T: Executable + 'static,
+  {
+    self.handle().try_run_all(tasks)
+  }
+
+  /// Run a collection of tasks
+  ///
+  /// The tasks are distributed across the runner's queues in turn, so that
+  /// large batches are spread evenly.
+  pub fn run_all<T, I>(&mut self, tasks: I)
+  where
+    T: Executable + 'static,
+    I: IntoIterator<Item = T>,
+  {
+    accepted(self.task_queue_set.push_all_round_robin(tasks.into_iter().collect()));
+  }
+
+  /// Run a collection of tasks, staggering their starts
+  ///
+  /// Each task is delayed by `spacing` more than the one before it, so the
+  /// first task starts right away and the last after `spacing` times one
+  /// less than the number of tasks. This helps avoid a batch of tasks
+  /// hitting the same resource all at once.
+  pub fn run_all_staggered<T, E>(&mut self, tasks: Vec<Task<'static, T, E>>, spacing: Duration)
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    accepted(self.handle().run_all_staggered(tasks, spacing));
+  }
+
+  /// Run a collection of tasks, balancing them by estimated cost
+  ///
+  /// Each task is placed on the queue with the lowest total estimated cost,
+  /// as reported by `Executable::estimated_cost`.
+  pub fn run_all_weighted<T>(&mut self, tasks: Vec<T>)
+  where
+    T: Executable + 'static,
+  {
+    accepted(self.handle().run_all_weighted(tasks));
+  }
+
+  /// Run a task, returning a handle to its result
+  ///
+  /// Unlike `run`, the result of the task is not dropped once it settles,
+  /// instead it is sent to the returned handle where it can be retrieved
+  /// from the calling thread.
+  pub fn spawn<T, E>(&mut self, task: Task<'static, T, E>) -> TaskHandle<T, E>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    accepted(self.handle().spawn(task))
+  }
+
+  /// Compute a value upon the runner, returning a handle to the result
+  ///
+  /// The closure is called once by one of the runner's workers. This is
+  /// like `thread::spawn`, but uses the runner's pooled threads.
+  pub fn compute<F, T>(&mut self, f: F) -> TaskHandle<T, ()>
+  where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+  {
+    self.spawn(Task::from_fn_once(move || State::Resolve(f())))
+  }
+
+  /// Run a task on the least loaded queue, returning a handle to its result
+  ///
+  /// Unlike `spawn`, which places the task on a random queue, the task is
+  /// placed on the queue with the fewest pending tasks.
+  pub fn spawn_balanced<T, E>(&mut self, task: Task<'static, T, E>) -> TaskHandle<T, E>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    accepted(self.handle().spawn_balanced(task))
+  }
+
+  /// Run a collection of tasks that may have failed to be constructed,
+  /// returning a handle to each result
+  ///
+  /// Handles are returned in the order given. The handle for a construction
+  /// error receives that error as a rejection straight away, while the rest
+  /// of the batch runs as normal.
+  pub fn spawn_all_results<T, E, I>(&mut self, tasks: I) -> Vec<TaskHandle<T, E>>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+    I: IntoIterator<Item = Result<Task<'static, T, E>, E>>,
+  {
+    accepted(self.handle().spawn_all_results(tasks))
+  }
+
+  /// Run a collection of tasks, sending each result to a channel as it
+  /// settles
+  ///
+  /// Results are sent in the order the tasks settle, which may differ from
+  /// the order of the tasks given. See the `channel` module for the channel
+  /// types used.
+  pub fn drain_into<T, E>(
+    &mut self,
+    tasks: Vec<Task<'static, T, E>>,
+    sender: &Sender<Result<T, E>>,
+  ) where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    accepted(self.handle().drain_into(tasks, sender));
+  }
+
+  /// Run a collection of tasks, routing their values and errors to separate
+  /// channels as they settle
+  ///
+  /// Both channels close once every task has settled.
+  pub fn drain_split<T, E>(&mut self, tasks: Vec<Task<'static, T, E>>) -> (Receiver<T>, Receiver<E>)
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    accepted(self.handle().drain_split(tasks))
+  }
+
+  /// Get the number of tasks waiting in each of the runner's queues
+  pub fn queue_depths(&self) -> Vec<usize> {
+    self.handle().queue_depths()
+  }
+
+  /// Get the number of tasks waiting across all of the runner's queues
+  ///
+  /// Tasks being moved between queues by a worker stealing work are counted,
+  /// while tasks being executed by a worker at the time of the call are not.
+  pub fn pending_len(&self) -> usize {
+    self.task_queue_set.pending_len()
+  }
+
+  /// Check if the runner has no outstanding tasks
+  ///
+  /// The runner is idle when every task run on it has settled or been
+  /// cleared, so no task is queued, executing, or being moved between
+  /// queues. As workers run concurrently with the caller, the result may
+  /// already be out of date by the time it is returned.
+  pub fn is_idle(&self) -> bool {
+    self.task_queue_set.outstanding_len() == 0
+  }
+
+  /// Run a task once a set of dependency tasks have settled
+  ///
+  /// The task will not begin executing until every handle given has received
+  /// its result. The results of the dependencies are discarded. Returns a
+  /// handle to the result of the task.
+  pub fn run_after_all<T, E, D, DE>(
+    &mut self,
+    mut deps: Vec<TaskHandle<D, DE>>,
+    mut task: Task<'static, T, E>,
+  ) -> TaskHandle<T, E>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+    D: Send + 'static,
+    DE: Send + 'static,
+  {
+    self.spawn(Task::new(move || {
+      deps.retain_mut(|dep| dep.poll().is_none());
+      if !deps.is_empty() {
+        return State::Pending;
+      }
+      match task.poll() {
+        Some(Ok(r)) => State::Resolve(r),
+        Some(Err(e)) => State::Reject(e),
+        None => State::Pending,
+      }
+    }))
+  }
+
+  /// Run a batch of items through a pipeline
+  ///
+  /// Each stage of the pipeline is run as its own task, along with a task
+  /// feeding the items into the first stage and a task collecting the
+  /// results from the last. Returns a handle to the result of each item, in
+  /// the same order as the items given.
+  pub fn run_pipeline<I, O, E>(
+    &mut self,
+    pipeline: &Pipeline<I, O, E>,
+    items: Vec<I>,
+  ) -> Vec<TaskHandle<O, E>>
+  where
+    I: Send + 'static,
+    O: Send + 'static,
+    E: Send + 'static,
+  {
+    let (tasks, handles) = pipeline.tasks(items);
     for task in tasks {
       self.run(task);
     }
+    handles
   }
 
-  pub fn finish(self) {
-    for worker in self.workers {
-      worker.finish();
+  /// Run a batch of tasks that borrow from the calling scope
+  ///
+  /// Unlike `run_all`, the tasks given do not need to be `'static`. This
+  /// method blocks until every task has settled and been dropped by the
+  /// runner, then returns the results in the same order as the tasks given.
+  /// A result will be `None` if its task was dropped before it settled.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the runner has no workers, as the tasks would never be
+  /// executed.
+  pub fn run_all_scoped<'env, T, E>(
+    &mut self,
+    tasks: Vec<Task<'env, T, E>>,
+  ) -> Vec<Option<Result<T, E>>>
+  where
+    T: Send + 'env,
+    E: Send + 'env,
+  {
+    assert!(self.worker_count() > 0, "Runner has no workers to run scoped tasks");
+    let (sender, receiver) = channel();
+    let mut batch = ScopedBatch {
+      receiver,
+      results: (0..tasks.len()).map(|_| None).collect(),
+      pushed_count: 0,
+    };
+
+    for (index, task) in tasks.into_iter().enumerate() {
+      let mut guard = ScopedTaskGuard {
+        index,
+        task: Some(task),
+        result: None,
+        sender: sender.clone(),
+      };
+      let task: Box<dyn Executable + 'env> = Box::new(Task::<(), ()>::new(move || guard.poll()));
+      // The task is extended to a static lifetime so it can be given to the
+      // workers. This is safe as the batch blocks until every task pushed
+      // has been dropped, even when unwinding, so nothing borrowed from 'env
+      // can outlive this call.
+      let task: Box<dyn Executable> = unsafe { mem::transmute(task) };
+      accepted(self.task_queue_set.push_to_rand_queue(task));
+      batch.pushed_count += 1;
+    }
+
+    batch.wait();
+    mem::take(&mut batch.results)
+  }
+
+  /// Finish the runner, waiting no longer than a given duration for the
+  /// queued tasks to drain
+  ///
+  /// If the tasks have not drained by the deadline the workers are told to
+  /// stop immediately, dropping the tasks remaining within their queues, and
+  /// the number of tasks dropped is returned as an error. A worker can only
+  /// stop between executions of its tasks, so a task that blocks within a
+  /// single execution will still delay the shutdown.
+  pub fn drain_timeout(self, dur: Duration) -> Result<(), usize> {
+    let deadline = Instant::now() + dur;
+    let (task_queue_set, workers) = self.stop();
+
+    while !workers.iter().all(|w| w.is_finished()) {
+      if Instant::now() >= deadline {
+        task_queue_set.abort();
+        break;
+      }
+      thread::sleep(Duration::from_millis(1));
+    }
+    for worker in workers {
+      worker.join();
+    }
+
+    match task_queue_set.len() {
+      0 => Ok(()),
+      undrained_count => Err(undrained_count),
     }
   }
+
+  /// Finish the runner, waiting no longer than a given duration for the
+  /// workers to exit
+  ///
+  /// Unlike `drain_timeout`, tasks are not dropped once the deadline passes.
+  /// Instead the workers still running are returned as an error, leaving the
+  /// caller to decide whether to join them. These workers have been told to
+  /// stop, so each exits once the tasks within its queue have settled.
+  pub fn finish_timeout(self, dur: Duration) -> Result<(), Vec<Worker>> {
+    let deadline = Instant::now() + dur;
+    let (_, mut workers) = self.stop();
+
+    loop {
+      let (finished, running): (Vec<_>, Vec<_>) =
+        workers.into_iter().partition(|w| w.is_finished());
+      for worker in finished {
+        worker.join();
+      }
+      if running.is_empty() {
+        return Ok(());
+      }
+      if Instant::now() >= deadline {
+        return Err(running);
+      }
+      workers = running;
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  /// Finish the runner, waiting for every task to settle
+  ///
+  /// Returns the number of tasks still pending once the workers have
+  /// exited, which is zero unless the runner had no workers to drain its
+  /// queues.
+  pub fn finish(self) -> usize {
+    let (task_queue_set, workers) = self.stop();
+    for worker in workers {
+      worker.join();
+    }
+    task_queue_set.len()
+  }
+
+  /// Finish the runner without waiting for pending tasks to settle
+  ///
+  /// Each worker exits once the task it is executing yields, and every task
+  /// left within the runner's queues is dropped. Returns the number of tasks
+  /// that were dropped.
+  pub fn shutdown_now(self) -> usize {
+    let (task_queue_set, workers) = self.stop();
+    task_queue_set.abort();
+    for worker in workers {
+      worker.join();
+    }
+    let discarded_count = task_queue_set.len();
+    task_queue_set.clear();
+    discarded_count
+  }
+
+  // Stop accepting tasks and tell each worker to stop once its queue drains
+  fn stop(self) -> (TaskQueueSet, Vec<Worker>) {
+    self.task_queue_set.shut_down();
+    if let Some(governor) = self.governor {
+      governor.finish();
+    }
+    if let Some(watchdog) = self.watchdog {
+      watchdog.finish();
+    }
+    let workers: Vec<_> = self.workers.lock().unwrap().drain(..).collect();
+    for worker in &workers {
+      worker.stop();
+    }
+    (self.task_queue_set, workers)
+  }
+}
+
+// A runner is only shut down once it has been finished, which consumes it,
+// so tasks submitted through the runner itself are always accepted
+fn accepted<T, U>(result: Result<T, U>) -> T {
+  match result {
+    Ok(v) => v,
+    Err(_) => unreachable!("Runner accepting tasks after shutting down"),
+  }
+}
+
+// The results of a batch of scoped tasks. Dropping the batch waits for each
+// task pushed to the runner to be dropped, so that a panic while pushing the
+// batch cannot leave the runner holding borrows from the calling scope.
+struct ScopedBatch<T, E> {
+  receiver: Receiver<(usize, Option<Result<T, E>>)>,
+  results: Vec<Option<Result<T, E>>>,
+  pushed_count: usize,
+}
+
+impl<T, E> ScopedBatch<T, E> {
+  fn wait(&mut self) {
+    // Tasks are pushed in order, so a task that was dropped without being
+    // pushed has an index past the pushed count and is not waited for
+    let mut remaining = self.pushed_count;
+    while remaining > 0 {
+      match self.receiver.recv() {
+        Ok((index, result)) if index < self.pushed_count => {
+          self.results[index] = result;
+          remaining -= 1;
+        }
+        Ok(_) => {}
+        Err(_) => break,
+      }
+    }
+    self.pushed_count = 0;
+  }
+}
+
+impl<T, E> Drop for ScopedBatch<T, E> {
+  fn drop(&mut self) {
+    self.wait();
+  }
+}
+
+struct ScopedTaskGuard<'env, T, E>
+where
+  T: Send + 'env,
+  E: Send + 'env,
+{
+  index: usize,
+  task: Option<Task<'env, T, E>>,
+  result: Option<Result<T, E>>,
+  sender: Sender<(usize, Option<Result<T, E>>)>,
+}
+
+impl<'env, T, E> ScopedTaskGuard<'env, T, E>
+where
+  T: Send,
+  E: Send,
+{
+  fn poll(&mut self) -> State<(), ()> {
+    if let Some(ref mut task) = self.task {
+      self.result = task.poll();
+    }
+    if self.result.is_some() {
+      State::Resolve(())
+    } else {
+      State::Pending
+    }
+  }
+}
+
+impl<'env, T, E> Drop for ScopedTaskGuard<'env, T, E>
+where
+  T: Send,
+  E: Send,
+{
+  fn drop(&mut self) {
+    // The task must be dropped before its result is sent, as sending the
+    // result releases the borrows held by the task.
+    self.task.take();
+    let _ = self.sender.send((self.index, self.result.take()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn can_spawn_task_and_wait_on_handle() {
+    let mut runner = Runner::with_worker_count(2);
+    let handle = runner.spawn(Task::<_, ()>::from(42));
+    assert_eq!(handle.wait(), Some(Ok(42)));
+    runner.finish();
+  }
+
+  #[test]
+  fn can_compute_value_on_runner() {
+    let mut runner = Runner::with_worker_count(2);
+    let handle = runner.compute(|| (1..=10).product::<u32>());
+    assert_eq!(handle.wait(), Some(Ok(3_628_800)));
+    runner.finish();
+  }
+
+  #[test]
+  fn can_spawn_tasks_that_failed_to_construct() {
+    let mut runner = Runner::with_worker_count(2);
+    let handles = runner.spawn_all_results(vec![
+      Ok(Task::from(1)),
+      Err("bad input"),
+      Ok(Task::new(|| State::Reject("failed"))),
+    ]);
+    let results: Vec<_> = handles.into_iter().map(|h| h.wait()).collect();
+    assert_eq!(results, vec![Some(Ok(1)), Some(Err("bad input")), Some(Err("failed"))]);
+    runner.finish();
+  }
+
+  #[test]
+  fn can_run_task_after_dependencies() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut runner = Runner::with_worker_count(2);
+    let settled = Arc::new(AtomicUsize::new(0));
+    let deps: Vec<_> = (1..4)
+      .map(|n| {
+        let settled = settled.clone();
+        let mut i = 0;
+        runner.spawn(Task::<_, ()>::new(move || {
+          i += 1;
+          if i < n * 100 {
+            return State::Pending;
+          }
+          settled.fetch_add(1, Ordering::SeqCst);
+          State::Resolve(())
+        }))
+      })
+      .collect();
+
+    let observed = settled.clone();
+    let handle = runner.run_after_all(
+      deps,
+      Task::<_, ()>::with(move || observed.load(Ordering::SeqCst)),
+    );
+    assert_eq!(handle.wait(), Some(Ok(3)));
+    runner.finish();
+  }
+
+  #[test]
+  fn can_run_borrowing_tasks_in_scope() {
+    let mut runner = Runner::with_worker_count(4);
+    let numbers = [1, 2, 3, 4, 5, 6, 7, 8];
+    let tasks: Vec<Task<i32, ()>> = numbers.iter().map(|n| Task::with(move || n * n)).collect();
+    let results: Vec<_> = runner
+      .run_all_scoped(tasks)
+      .into_iter()
+      .map(|r| r.unwrap().unwrap())
+      .collect();
+    assert_eq!(results, vec![1, 4, 9, 16, 25, 36, 49, 64]);
+    runner.finish();
+  }
+
+  #[test]
+  #[should_panic(expected = "Runner has no workers to run scoped tasks")]
+  fn run_all_scoped_panics_without_workers() {
+    let mut runner = Runner::with_worker_count(0);
+    runner.run_all_scoped(vec![Task::<(), ()>::with(|| ())]);
+  }
+
+  #[test]
+  fn scoped_batch_waits_for_pushed_tasks_when_dropped() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let (sender, receiver) = channel();
+    let batch = ScopedBatch::<(), ()> {
+      receiver,
+      results: vec![None, None],
+      pushed_count: 1,
+    };
+    let is_dropped = Arc::new(AtomicBool::new(false));
+    let task_is_dropped = is_dropped.clone();
+    let task = thread::spawn(move || {
+      // A task that was never pushed does not release the batch
+      sender.send((1, None)).unwrap();
+      thread::sleep(Duration::from_millis(20));
+      task_is_dropped.store(true, Ordering::SeqCst);
+      sender.send((0, None)).unwrap();
+    });
+    drop(batch);
+    assert!(is_dropped.load(Ordering::SeqCst));
+    task.join().unwrap();
+  }
+
+  #[test]
+  fn can_subscribe_to_task_lifecycle() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut runner = Runner::with_worker_count(2);
+    let start_events = events.clone();
+    runner.on_task_start(move |name| {
+      start_events.lock().unwrap().push(format!("start {}", name.unwrap()));
+    });
+    let complete_events = events.clone();
+    runner.on_task_complete(move |name, _| {
+      complete_events.lock().unwrap().push(format!("complete {}", name.unwrap()));
+    });
+
+    for i in 0..10 {
+      let mut polls = 0;
+      let task = Task::<(), ()>::new(move || {
+        polls += 1;
+        if polls < 10 {
+          State::Pending
+        } else {
+          State::Resolve(())
+        }
+      });
+      runner.run(task.named(format!("task-{}", i)));
+    }
+    runner.finish();
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 20);
+    for i in 0..10 {
+      let start = events.iter().position(|e| *e == format!("start task-{}", i));
+      let complete = events.iter().position(|e| *e == format!("complete task-{}", i));
+      assert!(start.unwrap() < complete.unwrap());
+    }
+  }
+
+  #[test]
+  fn worker_survives_panicking_task() {
+    let panicked = Arc::new(Mutex::new(Vec::new()));
+    let mut runner = Runner::with_worker_count(1);
+    let panic_events = panicked.clone();
+    runner.on_task_panic(move |name, _| {
+      panic_events.lock().unwrap().push(name.unwrap().to_string());
+    });
+
+    runner.run(Task::<(), ()>::new(|| panic!("task failed")).named("panicking"));
+    let handle = runner.spawn(Task::<_, ()>::from(42));
+    assert_eq!(handle.wait(), Some(Ok(42)));
+    runner.finish();
+
+    assert_eq!(*panicked.lock().unwrap(), vec!["panicking"]);
+  }
+
+  #[test]
+  fn runs_high_priority_tasks_first() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let released = Arc::new(AtomicBool::new(false));
+    let completed = Arc::new(Mutex::new(Vec::new()));
+    let mut runner = Runner::with_worker_count(1);
+    let gate_released = released.clone();
+    runner.run_with_priority(
+      Task::<(), ()>::new(move || {
+        if gate_released.load(Ordering::SeqCst) {
+          State::Resolve(())
+        } else {
+          State::Pending
+        }
+      }),
+      Priority::High,
+    );
+    for i in 0..10 {
+      let priority = if i % 2 == 0 { Priority::High } else { Priority::Low };
+      let completed = completed.clone();
+      runner.run_with_priority(
+        Task::<(), ()>::with(move || completed.lock().unwrap().push(priority)),
+        priority,
+      );
+    }
+    released.store(true, Ordering::SeqCst);
+    runner.finish();
+
+    let completed = completed.lock().unwrap();
+    assert_eq!(completed[..5], [Priority::High; 5]);
+    assert_eq!(completed[5..], [Priority::Low; 5]);
+  }
+
+  #[test]
+  fn becomes_idle_once_tasks_settle() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let released = Arc::new(AtomicBool::new(false));
+    let settled = Arc::new(AtomicUsize::new(0));
+    let mut runner = Runner::with_worker_count(2);
+    assert!(runner.is_idle());
+    for _ in 0..20 {
+      let released = released.clone();
+      let settled = settled.clone();
+      runner.run(Task::<(), ()>::new(move || {
+        if !released.load(Ordering::SeqCst) {
+          return State::Pending;
+        }
+        settled.fetch_add(1, Ordering::SeqCst);
+        State::Resolve(())
+      }));
+    }
+    assert!(!runner.is_idle());
+
+    released.store(true, Ordering::SeqCst);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !runner.is_idle() {
+      assert!(Instant::now() < deadline, "runner never became idle");
+      thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(runner.pending_len(), 0);
+    runner.finish();
+
+    assert_eq!(settled.load(Ordering::SeqCst), 20);
+  }
+
+  #[test]
+  fn is_not_idle_while_tasks_are_stolen() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let unsettled = Arc::new(AtomicUsize::new(0));
+    let mut runner = Runner::with_worker_count(4);
+    for round in 0..20 {
+      for _ in 0..200 {
+        let unsettled = unsettled.clone();
+        let mut polls_left = round % 5;
+        unsettled.fetch_add(1, Ordering::SeqCst);
+        // Tasks left pending are requeued, giving idle workers a queue long
+        // enough to steal from
+        runner.run(Task::<(), ()>::new(move || {
+          if polls_left > 0 {
+            polls_left -= 1;
+            return State::Pending;
+          }
+          unsettled.fetch_sub(1, Ordering::SeqCst);
+          State::Resolve(())
+        }));
+      }
+      let deadline = Instant::now() + Duration::from_secs(5);
+      while !runner.is_idle() {
+        assert!(Instant::now() < deadline, "runner never became idle");
+      }
+      assert_eq!(unsettled.load(Ordering::SeqCst), 0);
+    }
+    runner.finish();
+  }
+
+  #[test]
+  fn can_cancel_running_task() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    let execs = Arc::new(AtomicUsize::new(0));
+    let task_execs = execs.clone();
+    let mut runner = Runner::with_worker_count(2);
+    let token = runner.run_cancellable(Task::<(), ()>::new(move || {
+      task_execs.fetch_add(1, Ordering::SeqCst);
+      State::Pending
+    }));
+    while execs.load(Ordering::SeqCst) == 0 {
+      thread::sleep(Duration::from_millis(1));
+    }
+    token.cancel();
+    runner.finish();
+
+    let final_execs = execs.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(execs.load(Ordering::SeqCst), final_execs);
+  }
+
+  #[test]
+  fn can_limit_concurrency_across_batches() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let settled = Arc::new(AtomicUsize::new(0));
+    let semaphore = Semaphore::new(3);
+    let mut runner = Runner::with_worker_count(4);
+
+    for _batch in 0..2 {
+      for _ in 0..6 {
+        let running = running.clone();
+        let max_running = max_running.clone();
+        let settled = settled.clone();
+        let mut polls = 0;
+        runner.run_limited(&semaphore, Task::<(), ()>::new(move || {
+          if polls == 0 {
+            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now_running, Ordering::SeqCst);
+          }
+          polls += 1;
+          if polls < 5 {
+            thread::sleep(Duration::from_millis(1));
+            return State::Pending;
+          }
+          running.fetch_sub(1, Ordering::SeqCst);
+          settled.fetch_add(1, Ordering::SeqCst);
+          State::Resolve(())
+        }));
+      }
+    }
+    while settled.load(Ordering::SeqCst) < 12 {
+      thread::yield_now();
+    }
+
+    assert!(max_running.load(Ordering::SeqCst) <= 3);
+    runner.finish();
+  }
+
+  #[test]
+  fn can_stagger_task_starts() {
+    let spacing = Duration::from_millis(20);
+    let submitted_at = Instant::now();
+    let (sender, receiver) = channel();
+    let tasks = (0..4u32)
+      .map(|i| {
+        let sender = sender.clone();
+        Task::<(), ()>::with(move || sender.send((i, submitted_at.elapsed())).unwrap())
+      })
+      .collect();
+    drop(sender);
+
+    let mut runner = Runner::with_worker_count(2);
+    runner.run_all_staggered(tasks, spacing);
+    let starts: Vec<_> = receiver.iter().collect();
+    assert_eq!(starts.len(), 4);
+    for (i, started_after) in starts {
+      assert!(started_after >= spacing * i);
+    }
+    runner.finish();
+  }
+
+  #[test]
+  fn try_run_rejects_tasks_once_queues_are_full() {
+    let (started_sender, started_receiver) = channel();
+    let (release_sender, release_receiver) = channel::<()>();
+    let mut runner = Runner::builder().worker_count(1).queue_capacity(1).build();
+    runner.run(Task::<(), ()>::with(move || {
+      started_sender.send(()).unwrap();
+      release_receiver.recv().unwrap();
+    }));
+    started_receiver.recv().unwrap();
+
+    assert!(runner.try_run(Task::<(), ()>::with(|| ()).named("queued")).is_ok());
+    match runner.try_run(Task::<(), ()>::with(|| ()).named("overflow")) {
+      Err(task) => assert_eq!(task.name(), Some("overflow")),
+      Ok(()) => panic!("task was accepted by a full queue"),
+    }
+    release_sender.send(()).unwrap();
+    assert_eq!(runner.finish(), 0);
+  }
+
+  #[test]
+  fn try_run_all_returns_tasks_that_do_not_fit() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let released = Arc::new(AtomicBool::new(false));
+    let executed = Arc::new(AtomicUsize::new(0));
+    let mut runner = Runner::builder().worker_count(1).queue_capacity(1).build();
+    let tasks: Vec<_> = (0..10)
+      .map(|_| {
+        let released = released.clone();
+        let executed = executed.clone();
+        let mut started = false;
+        Task::<(), ()>::new(move || {
+          if !started {
+            started = true;
+            executed.fetch_add(1, Ordering::SeqCst);
+          }
+          if released.load(Ordering::SeqCst) {
+            State::Resolve(())
+          } else {
+            State::Pending
+          }
+        })
+      })
+      .collect();
+
+    let leftovers = runner.try_run_all(tasks);
+    released.store(true, Ordering::SeqCst);
+    runner.finish();
+
+    assert!(!leftovers.is_empty());
+    assert_eq!(leftovers.len() + executed.load(Ordering::SeqCst), 10);
+  }
+
+  #[test]
+  fn can_count_tasks_by_label() {
+    use std::thread;
+
+    let mut runner = Runner::with_worker_count(2);
+    for i in 0..8 {
+      let label = if i % 4 == 0 { "a" } else { "b" };
+      runner.run(Task::<(), ()>::labeled(label, || State::Resolve(())));
+    }
+    while runner.label_stats().values().map(|s| s.executed).sum::<usize>() < 8 {
+      thread::yield_now();
+    }
+
+    let label_stats = runner.label_stats();
+    assert_eq!(label_stats["a"], LabelStats { executed: 2, in_flight: 0 });
+    assert_eq!(label_stats["b"], LabelStats { executed: 6, in_flight: 0 });
+    runner.finish();
+  }
+
+  #[cfg(feature = "prometheus")]
+  #[test]
+  fn can_render_metrics_text() {
+    use std::thread;
+
+    let mut runner = Runner::with_worker_count(2);
+    runner.run(Task::<(), ()>::labeled("import", || State::Resolve(())));
+    while runner.label_stats().get("import").map_or(0, |s| s.executed) < 1 {
+      thread::yield_now();
+    }
+
+    let text = runner.metrics_text();
+    assert!(text.contains("task_kit_workers 2\n"));
+    assert!(text.contains("task_kit_queue_depth{queue=\"0\"}"));
+    let executed = text
+      .lines()
+      .find(|line| line.starts_with("task_kit_tasks_executed_total{label=\"import\"}"))
+      .and_then(|line| line.split(' ').nth(1))
+      .and_then(|value| value.parse::<usize>().ok());
+    assert_eq!(executed, Some(1));
+    runner.finish();
+  }
+
+  #[test]
+  fn can_finish_runner_without_tasks() {
+    let started_at = Instant::now();
+    Runner::with_worker_count(4).finish();
+    assert_eq!(Runner::with_worker_count(4).drain_timeout(Duration::from_secs(5)), Ok(()));
+    assert!(started_at.elapsed() < Duration::from_secs(1));
+  }
+
+  #[test]
+  fn drain_timeout_aborts_tasks_that_never_settle() {
+    use std::sync::mpsc;
+
+    let (polled_sender, polled_receiver) = mpsc::channel();
+    let (settled_sender, settled_receiver) = mpsc::channel();
+    let mut runner = Runner::with_worker_count(2);
+    runner.run(Task::<(), ()>::new(move || {
+      let _ = polled_sender.send(());
+      State::Pending
+    }));
+    runner.run(Task::<(), ()>::new(move || {
+      settled_sender.send(()).unwrap();
+      State::Resolve(())
+    }));
+
+    // Only drain once the first task is known to be queued or executing and
+    // the second has settled, so exactly one task is left to be aborted
+    polled_receiver.recv().unwrap();
+    settled_receiver.recv().unwrap();
+
+    let started_at = Instant::now();
+    assert_eq!(runner.drain_timeout(Duration::from_millis(50)), Err(1));
+    assert!(started_at.elapsed() < Duration::from_secs(5));
+  }
+
+  #[test]
+  fn finish_timeout_returns_workers_still_running() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let released = Arc::new(AtomicBool::new(false));
+    let task_released = released.clone();
+    let mut runner = Runner::with_worker_count(1);
+    runner.run(Task::<(), ()>::new(move || {
+      if task_released.load(Ordering::SeqCst) {
+        State::Resolve(())
+      } else {
+        State::Pending
+      }
+    }));
+
+    let started_at = Instant::now();
+    let workers = runner.finish_timeout(Duration::from_millis(50)).unwrap_err();
+    assert!(started_at.elapsed() >= Duration::from_millis(50));
+    assert_eq!(workers.len(), 1);
+
+    released.store(true, Ordering::SeqCst);
+    for worker in workers {
+      worker.join();
+    }
+  }
+
+  #[test]
+  fn shutdown_now_discards_pending_tasks() {
+    let mut runner = Runner::with_worker_count(2);
+    for _ in 0..100 {
+      runner.run(Task::<(), ()>::new(|| State::Pending));
+    }
+    assert_eq!(runner.shutdown_now(), 100);
+  }
+
+  #[test]
+  fn finish_reports_no_pending_tasks_once_drained() {
+    let mut runner = Runner::with_worker_count(2);
+    runner.run_all((0..10).map(|_| Task::<(), ()>::from(())));
+    assert_eq!(runner.finish(), 0);
+  }
+
+  #[test]
+  fn finish_timeout_finishes_once_drained() {
+    let mut runner = Runner::with_worker_count(2);
+    runner.run(Task::<(), ()>::from(()));
+    assert!(runner.finish_timeout(Duration::from_secs(5)).is_ok());
+  }
+
+  #[test]
+  fn drain_timeout_finishes_once_drained() {
+    let mut runner = Runner::with_worker_count(2);
+    runner.run_all((0..10).map(|_| Task::<(), ()>::from(())));
+    assert_eq!(runner.drain_timeout(Duration::from_secs(5)), Ok(()));
+  }
+
+  #[test]
+  fn can_wait_on_task_queued_behind_current_task() {
+    let mut runner = Runner::with_worker_count(1);
+    let handle = runner.handle();
+    let outer = runner.spawn(Task::<_, ()>::from_fn_once(move || {
+      let inner = handle.spawn(Task::<_, ()>::from(21)).unwrap();
+      match inner.wait() {
+        Some(Ok(value)) => State::Resolve(value * 2),
+        _ => State::Reject(()),
+      }
+    }));
+    assert_eq!(outer.wait(), Some(Ok(42)));
+    runner.finish();
+  }
+
+  #[test]
+  fn can_drain_results_into_channel() {
+    let mut runner = Runner::with_worker_count(2);
+    let (sender, receiver) = channel();
+    runner.drain_into((0..5).map(Task::<_, ()>::from).collect(), &sender);
+    let mut results: Vec<_> = receiver.iter().take(5).map(|r| r.unwrap()).collect();
+    results.sort();
+    assert_eq!(results, vec![0, 1, 2, 3, 4]);
+    runner.finish();
+  }
+
+  #[test]
+  fn can_drain_values_and_errors_separately() {
+    let mut runner = Runner::with_worker_count(2);
+    let tasks = (0..10)
+      .map(|i| Task::new(move || if i % 3 == 0 { State::Reject(i) } else { State::Resolve(i) }))
+      .collect();
+    let (ok_receiver, err_receiver) = runner.drain_split(tasks);
+    assert_eq!(ok_receiver.iter().count(), 6);
+    assert_eq!(err_receiver.iter().count(), 4);
+    runner.finish();
+  }
+
+  #[cfg(feature = "crossbeam_channel")]
+  #[test]
+  fn can_select_over_results_of_many_runners() {
+    use crossbeam_channel::select;
+
+    let mut runner_a = Runner::with_worker_count(1);
+    let mut runner_b = Runner::with_worker_count(1);
+    let (sender_a, receiver_a) = channel();
+    let (sender_b, receiver_b) = channel();
+    runner_a.drain_into((0..3).map(Task::<_, ()>::from).collect(), &sender_a);
+    runner_b.drain_into((10..13).map(Task::<_, ()>::from).collect(), &sender_b);
+
+    let mut results = Vec::new();
+    while results.len() < 6 {
+      select! {
+        recv(receiver_a) -> result => results.push(result.unwrap().unwrap()),
+        recv(receiver_b) -> result => results.push(result.unwrap().unwrap()),
+      }
+    }
+    results.sort();
+    assert_eq!(results, vec![0, 1, 2, 10, 11, 12]);
+    runner_a.finish();
+    runner_b.finish();
+  }
+
+  #[test]
+  fn can_run_loop() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut runner = Runner::with_worker_count(2);
+    let count = Arc::new(AtomicUsize::new(0));
+    let loop_count = count.clone();
+    runner.run_loop(move || loop_count.fetch_add(1, Ordering::SeqCst) + 1 == 10);
+    runner.finish();
+    assert_eq!(count.load(Ordering::SeqCst), 10);
+  }
+
+  #[test]
+  fn can_run_large_batch() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut runner = Runner::with_worker_count(4);
+    let count = Arc::new(AtomicUsize::new(0));
+    runner.run_all((0..10_000).map(|_| {
+      let count = count.clone();
+      let mut polls = 0;
+      Task::<(), ()>::new(move || {
+        polls += 1;
+        if polls < 3 {
+          return State::Pending;
+        }
+        count.fetch_add(1, Ordering::SeqCst);
+        State::Resolve(())
+      })
+    }));
+    assert_eq!(runner.finish(), 0);
+    assert_eq!(count.load(Ordering::SeqCst), 10_000);
+  }
+
+  #[test]
+  fn runs_every_task_accepted_while_finishing() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let runner = Runner::with_worker_count(4);
+    let handle = runner.handle();
+    let count = Arc::new(AtomicUsize::new(0));
+    let submitted_count = Arc::new(AtomicUsize::new(0));
+    let submitter_count = count.clone();
+    let submitter_submitted_count = submitted_count.clone();
+    let submitter = thread::spawn(move || loop {
+      let count = submitter_count.clone();
+      let mut polls = 0;
+      let task = Task::<(), ()>::new(move || {
+        polls += 1;
+        if polls < 3 {
+          return State::Pending;
+        }
+        count.fetch_add(1, Ordering::SeqCst);
+        State::Resolve(())
+      });
+      if handle.run(task).is_err() {
+        break;
+      }
+      submitter_submitted_count.fetch_add(1, Ordering::SeqCst);
+    });
+
+    // Finish while tasks are still being submitted from the other thread
+    while submitted_count.load(Ordering::SeqCst) < 1_000 {
+      thread::yield_now();
+    }
+    assert_eq!(runner.finish(), 0);
+    submitter.join().unwrap();
+
+    assert_eq!(count.load(Ordering::SeqCst), submitted_count.load(Ordering::SeqCst));
+  }
+
+  #[test]
+  fn can_run_tasks_with_recycled_allocations() {
+    let mut runner = Runner::builder()
+      .worker_count(4)
+      .recycle_task_allocations(true)
+      .build();
+    let handles: Vec<_> = (0..10_000)
+      .map(|n| {
+        let mut polls = 0;
+        runner.spawn(Task::<_, ()>::new(move || {
+          polls += 1;
+          match polls < 3 {
+            true => State::Pending,
+            false => State::Resolve(n * 2),
+          }
+        }))
+      })
+      .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.wait().unwrap()).collect();
+    runner.finish();
+
+    assert_eq!(results, (0..10_000).map(|n| Ok(n * 2)).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn can_detect_tasks_waiting_on_each_other() {
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    let mut runner = Runner::builder()
+      .worker_count(2)
+      .deadlock_detection(Duration::from_millis(50))
+      .build();
+    let (suspected_sender, suspected_receiver) = mpsc::channel();
+    let suspected_sender = Mutex::new(suspected_sender);
+    runner.on_deadlock_suspected(move |names| {
+      let _ = suspected_sender.lock().unwrap().send(names.to_vec());
+    });
+
+    let (a_sender, a_receiver) = mpsc::channel::<()>();
+    let (b_sender, b_receiver) = mpsc::channel::<()>();
+    let a = Task::<(), ()>::new(move || match b_receiver.try_recv() {
+      Ok(()) => {
+        let _ = a_sender.send(());
+        State::Resolve(())
+      }
+      Err(_) => State::Pending,
+    });
+    let b = Task::<(), ()>::new(move || match a_receiver.try_recv() {
+      Ok(()) => {
+        let _ = b_sender.send(());
+        State::Resolve(())
+      }
+      Err(_) => State::Pending,
+    });
+    let started_at = Instant::now();
+    runner.run(a.named("a"));
+    runner.run(b.named("b"));
+
+    let names = suspected_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(names, vec!["a", "b"]);
+    assert!(started_at.elapsed() >= Duration::from_millis(50));
+    assert_eq!(runner.drain_timeout(Duration::from_millis(10)), Err(2));
+  }
+
+  #[test]
+  fn can_name_worker_threads() {
+    use std::thread;
+
+    let mut runner = Runner::builder()
+      .worker_count(2)
+      .thread_name_prefix("task-kit-worker")
+      .stack_size(4 * 1024 * 1024)
+      .build();
+    runner.resize(3);
+    let handles: Vec<_> = (0..20)
+      .map(|_| runner.spawn(Task::<_, ()>::with(|| thread::current().name().map(String::from))))
+      .collect();
+    for handle in handles {
+      let name = handle.wait().unwrap().unwrap().unwrap();
+      assert!(name.starts_with("task-kit-worker-"), "unexpected thread name {}", name);
+    }
+    runner.finish();
+  }
+
+  #[test]
+  fn does_not_steal_from_queues_below_min_steal_len() {
+    let (started_sender, started_receiver) = channel();
+    let (release_sender, release_receiver) = channel::<()>();
+    let ran_on = Arc::new(Mutex::new(Vec::new()));
+    let runner = Runner::builder().worker_count(2).min_steal_len(3).build();
+    {
+      let workers = runner.workers.lock().unwrap();
+      let blocker_ran_on = ran_on.clone();
+      workers[0].run(Task::<(), ()>::with(move || {
+        blocker_ran_on.lock().unwrap().push(thread::current().id());
+        started_sender.send(()).unwrap();
+        release_receiver.recv().unwrap();
+      }));
+      started_receiver.recv().unwrap();
+      for _ in 0..2 {
+        let ran_on = ran_on.clone();
+        workers[0].run(Task::<(), ()>::with(move || {
+          ran_on.lock().unwrap().push(thread::current().id());
+        }));
+      }
+    }
+    thread::sleep(Duration::from_millis(50));
+    release_sender.send(()).unwrap();
+    runner.finish();
+
+    let ran_on = ran_on.lock().unwrap();
+    assert_eq!(ran_on.len(), 3);
+    assert!(ran_on.iter().all(|id| *id == ran_on[0]));
+  }
+
+  #[test]
+  fn can_resize_workers() {
+    let mut runner = Runner::with_worker_count(2);
+    assert_eq!(runner.worker_count(), 2);
+    runner.resize(4);
+    assert_eq!(runner.worker_count(), 4);
+    runner.resize(1);
+    assert_eq!(runner.worker_count(), 1);
+    assert_eq!(runner.spawn(Task::<_, ()>::from(1)).wait(), Some(Ok(1)));
+    runner.finish();
+  }
+
+  #[test]
+  fn can_shrink_workers_with_pending_tasks() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let released = Arc::new(AtomicBool::new(false));
+    let settled = Arc::new(AtomicUsize::new(0));
+    let mut runner = Runner::with_worker_count(4);
+    for _ in 0..20 {
+      let released = released.clone();
+      let settled = settled.clone();
+      runner.run(Task::<(), ()>::new(move || {
+        if !released.load(Ordering::SeqCst) {
+          return State::Pending;
+        }
+        settled.fetch_add(1, Ordering::SeqCst);
+        State::Resolve(())
+      }));
+    }
+
+    runner.resize(1);
+    assert_eq!(runner.worker_count(), 1);
+    released.store(true, Ordering::SeqCst);
+    runner.finish();
+    assert_eq!(settled.load(Ordering::SeqCst), 20);
+  }
+
+  #[test]
+  fn can_autoscale_workers() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn wait_for_worker_count(runner: &Runner, n: usize) {
+      let deadline = Instant::now() + Duration::from_secs(10);
+      while runner.worker_count() != n {
+        assert!(Instant::now() < deadline, "worker count never reached {}", n);
+        thread::sleep(Duration::from_millis(1));
+      }
+    }
+
+    let released = Arc::new(AtomicBool::new(false));
+    let mut runner = Runner::builder().worker_count(1).autoscale(1, 4).build();
+    for _ in 0..100 {
+      let released = released.clone();
+      runner.run(Task::<(), ()>::new(move || {
+        if released.load(Ordering::SeqCst) {
+          State::Resolve(())
+        } else {
+          State::Pending
+        }
+      }));
+    }
+    wait_for_worker_count(&runner, 4);
+    assert_eq!(runner.worker_count(), 4);
+
+    released.store(true, Ordering::SeqCst);
+    wait_for_worker_count(&runner, 1);
+    assert!(runner.is_idle());
+    runner.finish();
+  }
+
+  #[cfg(feature = "futures_support")]
+  #[test]
+  fn can_drive_handle_as_future() {
+    use futures::Future;
+
+    let mut runner = Runner::with_worker_count(2);
+    let mut i = 0;
+    let handle = runner.spawn(Task::<_, ()>::new(move || {
+      i += 1;
+      if i == 100 {
+        State::Resolve(i)
+      } else {
+        State::Pending
+      }
+    }));
+    assert_eq!(handle.map(|r| r.map(|v| v * 2)).wait(), Ok(Ok(200)));
+    runner.finish();
+  }
+
+  #[cfg(feature = "futures_support")]
+  #[test]
+  fn handle_future_is_canceled_if_task_is_dropped() {
+    use futures::Future;
+    use futures::sync::oneshot::Canceled;
+
+    let mut runner = Runner::with_worker_count(1);
+    let handle = runner.spawn(Task::<(), ()>::new(|| State::Pending));
+    let shutdown = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(20));
+      runner.shutdown_now();
+    });
+    // Blocks until the dropped task's sender notifies the waiting future
+    assert_eq!(Future::wait(handle), Err(Canceled));
+    shutdown.join().unwrap();
+  }
 }
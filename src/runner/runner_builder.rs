@@ -0,0 +1,131 @@
+use std::time::Duration;
+use num_cpus;
+use super::Runner;
+
+/// A builder for configuring a runner.
+///
+/// # Examples
+///
+/// ```
+/// # use task_kit::prelude::*;
+/// let runner = Runner::builder().worker_count(4).build();
+/// # runner.finish();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RunnerBuilder {
+  pub(crate) worker_count: usize,
+  pub(crate) autoscale: Option<(usize, usize)>,
+  pub(crate) queue_capacity: Option<usize>,
+  pub(crate) min_steal_len: usize,
+  pub(crate) deadlock_detection: Option<Duration>,
+  pub(crate) thread_name_prefix: Option<String>,
+  pub(crate) stack_size: Option<usize>,
+  pub(crate) recycle_task_allocations: bool,
+}
+
+impl RunnerBuilder {
+  pub fn new() -> Self {
+    Self {
+      worker_count: num_cpus::get() + 1,
+      autoscale: None,
+      queue_capacity: None,
+      min_steal_len: 2,
+      deadlock_detection: None,
+      thread_name_prefix: None,
+      stack_size: None,
+      recycle_task_allocations: false,
+    }
+  }
+
+  /// Set the number of workers the runner starts with
+  pub fn worker_count(mut self, n: usize) -> Self {
+    self.worker_count = n;
+    self
+  }
+
+  /// Scale the number of workers between `min` and `max` according to load
+  ///
+  /// Workers are added while tasks are queued faster than they can be
+  /// executed, and removed while the runner is idle.
+  pub fn autoscale(mut self, min: usize, max: usize) -> Self {
+    self.autoscale = Some((min, max));
+    self
+  }
+
+  /// Limit the number of tasks each worker's queue will accept
+  ///
+  /// The limit applies to tasks submitted with `try_run` and `try_run_all`,
+  /// which hand tasks back once every queue is full.
+  pub fn queue_capacity(mut self, n: usize) -> Self {
+    self.queue_capacity = Some(n);
+    self
+  }
+
+  /// Set the minimum length of a queue that idle workers will steal from
+  ///
+  /// Idle workers steal half of the tasks from another worker's queue.
+  /// Raising the minimum avoids contending over queues too short to be worth
+  /// splitting. Defaults to 2, which is also the lowest minimum allowed.
+  pub fn min_steal_len(mut self, n: usize) -> Self {
+    self.min_steal_len = n;
+    self
+  }
+
+  /// Warn if tasks are in flight but none settle for a given duration
+  ///
+  /// This helps to diagnose tasks that wait upon each other and so never
+  /// settle. The warning is written to stderr along with the names of the
+  /// tasks in flight, and passed to any callbacks registered with
+  /// `Runner::on_deadlock_suspected`. Nothing is done to resolve the
+  /// deadlock.
+  pub fn deadlock_detection(mut self, timeout: Duration) -> Self {
+    self.deadlock_detection = Some(timeout);
+    self
+  }
+
+  /// Name worker threads with a given prefix
+  ///
+  /// Each worker thread is named with the prefix followed by a number, for
+  /// example `prefix-0`, which makes them easy to identify when profiling.
+  /// By default worker threads are unnamed.
+  pub fn thread_name_prefix<S>(mut self, prefix: S) -> Self
+  where
+    S: Into<String>,
+  {
+    self.thread_name_prefix = Some(prefix.into());
+    self
+  }
+
+  /// Set the stack size of worker threads in bytes
+  ///
+  /// By default worker threads use the standard library's default stack
+  /// size. Tasks that recurse deeply may need a larger stack.
+  pub fn stack_size(mut self, size: usize) -> Self {
+    self.stack_size = Some(size);
+    self
+  }
+
+  /// Recycle the allocations of settled tasks for new tasks
+  ///
+  /// Every task given to the runner is boxed. With recycling enabled, the
+  /// boxes of settled tasks are kept in a pool shared by the workers, grouped
+  /// by size, and reused for the tasks that follow rather than being freed.
+  /// The pool keeps a bounded number of allocations of each size. This can
+  /// reduce allocator pressure when submitting very many small tasks.
+  /// Disabled by default.
+  pub fn recycle_task_allocations(mut self, enabled: bool) -> Self {
+    self.recycle_task_allocations = enabled;
+    self
+  }
+
+  /// Build the runner
+  pub fn build(self) -> Runner {
+    Runner::from_builder(self)
+  }
+}
+
+impl Default for RunnerBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
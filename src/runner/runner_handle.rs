@@ -0,0 +1,501 @@
+use std::fmt::{self, Debug};
+use std::time::Duration;
+use super::channel::{channel, Receiver, Sender};
+use super::Executable;
+use super::HandleSender;
+use super::LoopTask;
+use super::SubmitError;
+use super::TaskHandle;
+use super::TaskQueueSet;
+use super::super::task::Task;
+
+type Tasks<T, E> = Vec<Task<'static, T, E>>;
+
+// Tasks, or the errors from failing to construct them
+type TaskResults<T, E> = Vec<Result<Task<'static, T, E>, E>>;
+
+type TaskHandles<T, E> = Vec<TaskHandle<T, E>>;
+
+type SplitReceivers<T, E> = (Receiver<T>, Receiver<E>);
+
+/// A cloneable handle for submitting tasks to a runner.
+///
+/// Runner handles can be shared between threads, allowing tasks to be
+/// submitted concurrently without moving the runner. The runner that
+/// produced the handle still controls the lifecycle of the thread pool.
+///
+/// # Examples
+///
+/// ```
+/// # use task_kit::prelude::*;
+/// # use std::thread;
+/// let runner = Runner::new();
+/// let handle = runner.handle();
+/// thread::spawn(move || {
+///   let task: Task<(), ()> = Task::with(|| ());
+///   handle.run(task).unwrap();
+/// }).join().unwrap();
+/// runner.finish();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RunnerHandle {
+  task_queue_set: TaskQueueSet,
+}
+
+impl RunnerHandle {
+  #[doc(hidden)]
+  pub fn new(task_queue_set: TaskQueueSet) -> Self {
+    Self { task_queue_set }
+  }
+
+  /// Run a task
+  ///
+  /// If the runner has begun to finish, the task is handed back within a
+  /// `SubmitError`, as it would otherwise never be executed. The same goes
+  /// for every other method that submits tasks through a handle.
+  pub fn run<T>(&self, task: T) -> Result<(), SubmitError<T>>
+  where
+    T: Executable + 'static,
+  {
+    self
+      .task_queue_set
+      .push_to_rand_queue(task)
+      .map_err(SubmitError::ShutdownInProgress)
+  }
+
+  /// Run a closure repeatedly until it returns true
+  ///
+  /// This is the simplest way to run side effects upon the runner, without
+  /// the need for a task or state.
+  pub fn run_loop<F>(&self, f: F) -> Result<(), SubmitError<F>>
+  where
+    F: FnMut() -> bool + Send + 'static,
+  {
+    self
+      .task_queue_set
+      .push_to_rand_queue(LoopTask::new(f))
+      .map_err(|task| SubmitError::ShutdownInProgress(task.into_inner()))
+  }
+
+  /// Run a task if there is room for it within the runner's queues
+  ///
+  /// If every queue is at the capacity configured with
+  /// `RunnerBuilder::queue_capacity`, or the runner has begun to finish, the
+  /// task is handed back.
+  pub fn try_run<T>(&self, task: T) -> Result<(), T>
+  where
+    T: Executable + 'static,
+  {
+    self.task_queue_set.try_push_to_rand_queue(task)
+  }
+
+  /// Run as many tasks as there is room for within the runner's queues
+  ///
+  /// Returns the tasks that did not fit, or every task if the runner has
+  /// begun to finish.
+  pub fn try_run_all<T>(&self, tasks: Vec<T>) -> Vec<T>
+  where
+    T: Executable + 'static,
+  {
+    tasks
+      .into_iter()
+      .filter_map(|task| self.try_run(task).err())
+      .collect()
+  }
+
+  /// Run a collection of tasks
+  ///
+  /// The tasks are distributed across the runner's queues in turn, so that
+  /// large batches are spread evenly. If the runner has begun to finish,
+  /// none of the tasks are run.
+  pub fn run_all<T, I>(&self, tasks: I) -> Result<(), SubmitError<Vec<T>>>
+  where
+    T: Executable + 'static,
+    I: IntoIterator<Item = T>,
+  {
+    self
+      .task_queue_set
+      .push_all_round_robin(tasks.into_iter().collect())
+      .map_err(SubmitError::ShutdownInProgress)
+  }
+
+  /// Run a collection of tasks, staggering their starts
+  ///
+  /// Each task is delayed with `Task::after` by `spacing` more than the one
+  /// before it, so the first task starts right away and the last after
+  /// `spacing` times one less than the number of tasks. This helps avoid a
+  /// batch of tasks hitting the same resource all at once. If the runner has
+  /// begun to finish, the delayed tasks are handed back.
+  pub fn run_all_staggered<T, E>(
+    &self,
+    tasks: Vec<Task<'static, T, E>>,
+    spacing: Duration,
+  ) -> Result<(), SubmitError<Tasks<T, E>>>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    let tasks = tasks
+      .into_iter()
+      .enumerate()
+      .map(|(i, task)| task.after(spacing * i as u32))
+      .collect();
+    self
+      .task_queue_set
+      .push_all_round_robin(tasks)
+      .map_err(SubmitError::ShutdownInProgress)
+  }
+
+  /// Run a collection of tasks, balancing them by estimated cost
+  ///
+  /// Each task is placed on the queue with the lowest total estimated cost,
+  /// as reported by `Executable::estimated_cost`.
+  pub fn run_all_weighted<T>(&self, tasks: Vec<T>) -> Result<(), SubmitError<Vec<T>>>
+  where
+    T: Executable + 'static,
+  {
+    self
+      .task_queue_set
+      .push_all_weighted(tasks)
+      .map_err(SubmitError::ShutdownInProgress)
+  }
+
+  /// Run a task, returning a handle to its result
+  ///
+  /// Unlike `run`, the result of the task is not dropped once it settles,
+  /// instead it is sent to the returned handle where it can be retrieved
+  /// from the calling thread.
+  pub fn spawn<T, E>(
+    &self,
+    task: Task<'static, T, E>,
+  ) -> Result<TaskHandle<T, E>, SubmitError<Task<'static, T, E>>>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    let (sender, handle) = TaskHandle::channel();
+    self
+      .task_queue_set
+      .push_to_rand_queue(Forward::to_handle(task, sender))
+      .map(|()| handle)
+      .map_err(|forward| SubmitError::ShutdownInProgress(forward.task))
+  }
+
+  /// Run a task on the least loaded queue, returning a handle to its result
+  ///
+  /// Unlike `spawn`, which places the task on a random queue, the task is
+  /// placed on the queue with the fewest pending tasks.
+  pub fn spawn_balanced<T, E>(
+    &self,
+    task: Task<'static, T, E>,
+  ) -> Result<TaskHandle<T, E>, SubmitError<Task<'static, T, E>>>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    let (sender, handle) = TaskHandle::channel();
+    self
+      .task_queue_set
+      .push_to_shallowest_queue(Forward::to_handle(task, sender))
+      .map(|()| handle)
+      .map_err(|forward| SubmitError::ShutdownInProgress(forward.task))
+  }
+
+  /// Run a collection of tasks that may have failed to be constructed,
+  /// returning a handle to each result
+  ///
+  /// Handles are returned in the order given. The handle for a construction
+  /// error receives that error as a rejection straight away, while the rest
+  /// of the batch runs as normal. If the runner has begun to finish, the
+  /// whole batch is handed back as given.
+  pub fn spawn_all_results<T, E, I>(
+    &self,
+    tasks: I,
+  ) -> Result<TaskHandles<T, E>, SubmitError<TaskResults<T, E>>>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+    I: IntoIterator<Item = Result<Task<'static, T, E>, E>>,
+  {
+    let mut handles = Vec::new();
+    let mut forwards = Vec::new();
+    let mut errors = Vec::new();
+    let mut is_task = Vec::new();
+    for task in tasks {
+      let (sender, handle) = TaskHandle::channel();
+      handles.push(handle);
+      is_task.push(task.is_ok());
+      match task {
+        Ok(task) => forwards.push(Forward::to_handle(task, sender)),
+        Err(e) => errors.push((e, sender)),
+      }
+    }
+
+    match self.task_queue_set.push_all_round_robin(forwards) {
+      Ok(()) => {
+        for (e, sender) in errors {
+          sender.send(Err(e));
+        }
+        Ok(handles)
+      }
+      Err(forwards) => {
+        let mut forwards = forwards.into_iter();
+        let mut errors = errors.into_iter();
+        let tasks = is_task
+          .into_iter()
+          .map(|is_task| match is_task {
+            true => Ok(forwards.next().unwrap().task),
+            false => Err(errors.next().unwrap().0),
+          })
+          .collect();
+        Err(SubmitError::ShutdownInProgress(tasks))
+      }
+    }
+  }
+
+  /// Run a collection of tasks, sending each result to a channel as it
+  /// settles
+  ///
+  /// Results are sent in the order the tasks settle, which may differ from
+  /// the order of the tasks given. See the `channel` module for the channel
+  /// types used.
+  pub fn drain_into<T, E>(
+    &self,
+    tasks: Vec<Task<'static, T, E>>,
+    sender: &Sender<Result<T, E>>,
+  ) -> Result<(), SubmitError<Tasks<T, E>>>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    let forwards = tasks
+      .into_iter()
+      .map(|task| Forward::to_sender(task, sender.clone()))
+      .collect();
+    self
+      .task_queue_set
+      .push_all_round_robin(forwards)
+      .map_err(|forwards| unwrap_all(forwards, |f| f.task))
+  }
+
+  /// Run a collection of tasks, routing their values and errors to separate
+  /// channels as they settle
+  ///
+  /// Both channels close once every task has settled.
+  pub fn drain_split<T, E>(
+    &self,
+    tasks: Vec<Task<'static, T, E>>,
+  ) -> Result<SplitReceivers<T, E>, SubmitError<Tasks<T, E>>>
+  where
+    T: Send + 'static,
+    E: Send + 'static,
+  {
+    let (ok_sender, ok_receiver) = channel();
+    let (err_sender, err_receiver) = channel();
+    let forwards = tasks
+      .into_iter()
+      .map(|task| {
+        let ok_sender = ok_sender.clone();
+        let err_sender = err_sender.clone();
+        Forward::new(task, move |result| match result {
+          Ok(r) => {
+            let _ = ok_sender.send(r);
+          }
+          Err(e) => {
+            let _ = err_sender.send(e);
+          }
+        })
+      })
+      .collect();
+    self
+      .task_queue_set
+      .push_all_round_robin(forwards)
+      .map(|()| (ok_receiver, err_receiver))
+      .map_err(|forwards| unwrap_all(forwards, |f| f.task))
+  }
+
+  /// Get the number of tasks waiting in each of the runner's queues
+  pub fn queue_depths(&self) -> Vec<usize> {
+    self.task_queue_set.queue_depths()
+  }
+}
+
+fn unwrap_all<W, T, F>(wrapped: Vec<W>, f: F) -> SubmitError<Vec<T>>
+where
+  F: FnMut(W) -> T,
+{
+  SubmitError::ShutdownInProgress(wrapped.into_iter().map(f).collect())
+}
+
+// An executable that passes the result of a task on once it settles. The
+// task is kept intact so that it can be handed back if the runner is
+// shutting down.
+struct Forward<T, E> {
+  task: Task<'static, T, E>,
+  forward: Box<dyn FnMut(Result<T, E>) + Send>,
+}
+
+impl<T, E> Forward<T, E>
+where
+  T: Send + 'static,
+  E: Send + 'static,
+{
+  fn new<F>(task: Task<'static, T, E>, forward: F) -> Self
+  where
+    F: FnMut(Result<T, E>) + Send + 'static,
+  {
+    Forward {
+      task,
+      forward: Box::new(forward),
+    }
+  }
+
+  fn to_sender(task: Task<'static, T, E>, sender: Sender<Result<T, E>>) -> Self {
+    Self::new(task, move |result| {
+      let _ = sender.send(result);
+    })
+  }
+
+  fn to_handle(task: Task<'static, T, E>, sender: HandleSender<T, E>) -> Self {
+    let mut sender = Some(sender);
+    Self::new(task, move |result| {
+      if let Some(sender) = sender.take() {
+        sender.send(result);
+      }
+    })
+  }
+}
+
+impl<T, E> Debug for Forward<T, E> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Forward {{ task: {:?} }}", self.task)
+  }
+}
+
+impl<T, E> Executable for Forward<T, E>
+where
+  T: Send + 'static,
+  E: Send + 'static,
+{
+  fn exec(&mut self) -> bool {
+    match self.task.poll() {
+      Some(result) => {
+        (self.forward)(result);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn name(&self) -> Option<&str> {
+    self.task.name()
+  }
+
+  fn label(&self) -> Option<&'static str> {
+    self.task.label()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::thread;
+  use super::*;
+  use super::super::Runner;
+  use super::super::super::task::State;
+
+  #[test]
+  fn can_submit_from_many_threads() {
+    let runner = Runner::with_worker_count(2);
+    let count = Arc::new(AtomicUsize::new(0));
+
+    let producers: Vec<_> = (0..4)
+      .map(|_| {
+        let handle = runner.handle();
+        let count = count.clone();
+        thread::spawn(move || {
+          for _ in 0..25 {
+            let count = count.clone();
+            handle
+              .run(Task::<(), ()>::with(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+              }))
+              .unwrap();
+          }
+        })
+      })
+      .collect();
+    for producer in producers {
+      producer.join().unwrap();
+    }
+    runner.finish();
+
+    assert_eq!(count.load(Ordering::SeqCst), 100);
+  }
+
+  #[test]
+  fn can_spawn_onto_shallowest_queue() {
+    let task_queue_set = TaskQueueSet::new();
+    let queues: Vec<_> = (0..3).map(|_| task_queue_set.new_queue()).collect();
+    for (queue, depth) in queues.iter().zip(&[3, 1, 2]) {
+      for _ in 0..*depth {
+        queue.insert(Box::new(Task::<(), ()>::new(|| State::Pending)));
+      }
+    }
+
+    let handle = RunnerHandle::new(task_queue_set);
+    handle.spawn_balanced(Task::<(), ()>::new(|| State::Pending)).unwrap();
+    assert_eq!(handle.queue_depths(), vec![3, 2, 2]);
+  }
+
+  #[test]
+  fn hands_back_tasks_submitted_after_shutdown() {
+    let runner = Runner::with_worker_count(1);
+    let handle = runner.handle();
+    runner.finish();
+
+    let task = Task::<(), ()>::with(|| ()).named("late");
+    match handle.run(task) {
+      Err(SubmitError::ShutdownInProgress(task)) => assert_eq!(task.name(), Some("late")),
+      Ok(()) => panic!("task was accepted after shutdown"),
+    }
+    let tasks = handle.run_all(vec![Task::<(), ()>::with(|| ()), Task::with(|| ())]);
+    assert_eq!(tasks.unwrap_err().into_inner().len(), 2);
+  }
+
+  #[test]
+  fn hands_back_tasks_from_every_submission_path_after_shutdown() {
+    use std::time::Duration;
+    use super::super::channel::channel;
+
+    let runner = Runner::with_worker_count(1);
+    let handle = runner.handle();
+    runner.finish();
+
+    let task = || Task::<u32, &str>::from(1).named("late");
+    let tasks = || vec![task(), task()];
+
+    let spawned = handle.spawn(task()).unwrap_err().into_inner();
+    assert_eq!(spawned.name(), Some("late"));
+    let spawned = handle.spawn_balanced(task()).unwrap_err().into_inner();
+    assert_eq!(spawned.name(), Some("late"));
+    assert!(handle.run_loop(|| true).is_err());
+    assert_eq!(handle.try_run(task()).unwrap_err().name(), Some("late"));
+    assert_eq!(handle.try_run_all(tasks()).len(), 2);
+    assert_eq!(handle.run_all_weighted(tasks()).unwrap_err().into_inner().len(), 2);
+    let staggered = handle.run_all_staggered(tasks(), Duration::from_millis(1));
+    let staggered = staggered.unwrap_err().into_inner();
+    assert_eq!(staggered.len(), 2);
+    assert_eq!(staggered[1].name(), Some("late"));
+    let (sender, _receiver) = channel();
+    assert_eq!(handle.drain_into(tasks(), &sender).unwrap_err().into_inner().len(), 2);
+    assert_eq!(handle.drain_split(tasks()).unwrap_err().into_inner().len(), 2);
+
+    let results = handle.spawn_all_results(vec![Ok(task()), Err("invalid"), Ok(task())]);
+    let results = results.unwrap_err().into_inner();
+    assert_eq!(results[0].as_ref().unwrap().name(), Some("late"));
+    assert_eq!(results[1].as_ref().err(), Some(&"invalid"));
+    assert_eq!(results[2].as_ref().unwrap().name(), Some("late"));
+  }
+}
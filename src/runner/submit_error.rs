@@ -0,0 +1,40 @@
+use std::error::Error;
+use std::fmt;
+
+/// The error returned when a task is submitted to a runner that is shutting
+/// down.
+///
+/// Tasks submitted through a `RunnerHandle` after the runner has begun to
+/// finish would never be executed, so they are handed back within the error
+/// instead.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SubmitError<T> {
+  ShutdownInProgress(T),
+}
+
+impl<T> SubmitError<T> {
+  /// Take back the task or tasks that were submitted
+  pub fn into_inner(self) -> T {
+    match self {
+      SubmitError::ShutdownInProgress(task) => task,
+    }
+  }
+}
+
+impl<T> fmt::Debug for SubmitError<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      SubmitError::ShutdownInProgress(_) => write!(f, "ShutdownInProgress(..)"),
+    }
+  }
+}
+
+impl<T> fmt::Display for SubmitError<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      SubmitError::ShutdownInProgress(_) => write!(f, "runner is shutting down"),
+    }
+  }
+}
+
+impl<T> Error for SubmitError<T> {}
@@ -0,0 +1,137 @@
+use std::fmt::{self, Debug};
+use std::alloc::Layout;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+use super::AllocPool;
+use super::Block;
+use super::Executable;
+
+/// A boxed task, optionally allocated from an `AllocPool`.
+///
+/// When given a pool, the task is written into a block taken from it, and
+/// the block is given back once the task is dropped so that its allocation
+/// can be reused by the next task of a similar size. Tasks without a size
+/// class in the pool, or created without a pool, are boxed as usual.
+#[doc(hidden)]
+pub struct TaskBox {
+  task: NonNull<dyn Executable>,
+  block: Option<(Block, AllocPool)>,
+}
+
+// The box uniquely owns its task, which is `Send` as every executable is.
+unsafe impl Send for TaskBox {}
+
+impl TaskBox {
+  pub fn new<T>(task: T, alloc_pool: Option<&AllocPool>) -> Self
+  where
+    T: Executable + 'static,
+  {
+    let block = alloc_pool.and_then(|p| p.take(Layout::new::<T>()).map(|b| (b, p.clone())));
+    match block {
+      // SAFETY: The block was taken for the layout of `T`, so it is large
+      // and aligned enough to hold the task, and is uniquely owned by this
+      // box. Writing the task initializes it, and the pointer to it is
+      // non-null as the block's allocation is.
+      Some((block, alloc_pool)) => unsafe {
+        let task_ptr = block.as_ptr() as *mut T;
+        ptr::write(task_ptr, task);
+        Self {
+          task: NonNull::new_unchecked(task_ptr as *mut dyn Executable),
+          block: Some((block, alloc_pool)),
+        }
+      },
+      None => Self::from(Box::new(task) as Box<dyn Executable>),
+    }
+  }
+}
+
+impl From<Box<dyn Executable>> for TaskBox {
+  fn from(task: Box<dyn Executable>) -> Self {
+    Self {
+      // SAFETY: `Box::into_raw` never returns a null pointer
+      task: unsafe { NonNull::new_unchecked(Box::into_raw(task)) },
+      block: None,
+    }
+  }
+}
+
+impl Deref for TaskBox {
+  type Target = dyn Executable;
+
+  fn deref(&self) -> &Self::Target {
+    // SAFETY: The task is initialized and owned by the box until it is
+    // dropped, and the borrow is tied to the box's
+    unsafe { self.task.as_ref() }
+  }
+}
+
+impl DerefMut for TaskBox {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    // SAFETY: The task is initialized and uniquely owned by the box, and
+    // the mutable borrow is tied to the box's
+    unsafe { self.task.as_mut() }
+  }
+}
+
+impl Drop for TaskBox {
+  fn drop(&mut self) {
+    match self.block.take() {
+      Some((block, alloc_pool)) => {
+        // SAFETY: The task was written into the block on creation and has
+        // not been dropped. It is dropped in place exactly once here, before
+        // the block holding it is given back for reuse.
+        unsafe { ptr::drop_in_place(self.task.as_ptr()) };
+        alloc_pool.give(block);
+      }
+      // SAFETY: Without a block, the task pointer came from
+      // `Box::into_raw` and has not been freed, so the box is rebuilt once
+      None => drop(unsafe { Box::from_raw(self.task.as_ptr()) }),
+    }
+  }
+}
+
+impl Debug for TaskBox {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    Debug::fmt(&**self, f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use super::*;
+  use super::super::super::task::{State, Task};
+
+  #[test]
+  fn recycles_allocations_of_dropped_tasks() {
+    let alloc_pool = AllocPool::new();
+    let drop_count = Arc::new(AtomicUsize::new(0));
+
+    struct CountDrop(Arc<AtomicUsize>);
+    impl Drop for CountDrop {
+      fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+      }
+    }
+
+    let guard = CountDrop(drop_count.clone());
+    let mut task_box = TaskBox::new(
+      Task::<(), ()>::new(move || {
+        let _ = &guard;
+        State::Resolve(())
+      }),
+      Some(&alloc_pool),
+    );
+    assert!(task_box.exec());
+    drop(task_box);
+
+    assert_eq!(drop_count.load(Ordering::SeqCst), 1);
+    assert_eq!(alloc_pool.free_len(), 1);
+
+    let task_box = TaskBox::new(Task::<(), ()>::new(|| State::Pending), Some(&alloc_pool));
+    assert_eq!(alloc_pool.free_len(), 0);
+    drop(task_box);
+    assert_eq!(alloc_pool.free_len(), 1);
+  }
+}
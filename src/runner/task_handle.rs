@@ -0,0 +1,151 @@
+use std::fmt::{self, Debug};
+use super::channel::{channel, Receiver, Sender, TryRecvError};
+use super::worker;
+
+#[cfg(feature = "futures_support")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "futures_support")]
+use futures::{task, Async, Future, Poll};
+#[cfg(feature = "futures_support")]
+use futures::sync::oneshot::Canceled;
+
+// The futures task last polling the handle, notified by the sending side
+#[cfg(feature = "futures_support")]
+type Waiter = Arc<Mutex<Option<task::Task>>>;
+
+/// A handle to a task spawned upon the runner.
+///
+/// The handle receives the result of the task once it settles within the
+/// thread pool. It can be polled, or waited upon from the calling thread.
+pub struct TaskHandle<T, E> {
+  receiver: Receiver<Result<T, E>>,
+  #[cfg(feature = "futures_support")]
+  waiter: Waiter,
+}
+
+impl<T, E> TaskHandle<T, E> {
+  /// Create a handle along with the sender for delivering its result
+  #[doc(hidden)]
+  pub fn channel() -> (HandleSender<T, E>, Self) {
+    let (sender, receiver) = channel();
+    #[cfg(feature = "futures_support")]
+    let waiter = Waiter::default();
+    let handle_sender = HandleSender {
+      sender: Some(sender),
+      #[cfg(feature = "futures_support")]
+      waiter: waiter.clone(),
+    };
+    let handle = Self {
+      receiver,
+      #[cfg(feature = "futures_support")]
+      waiter,
+    };
+    (handle_sender, handle)
+  }
+
+  /// Checks if the task has settled without blocking
+  ///
+  /// If the task resolved or rejected then the returned option will contain
+  /// its result.
+  pub fn poll(&mut self) -> Option<Result<T, E>> {
+    self.receiver.try_recv().ok()
+  }
+
+  /// Blocks the calling thread until the task settles
+  ///
+  /// Returns `None` if the task was dropped by the runner before it settled.
+  /// If called from within a task executing on the runner, the worker
+  /// executes other queued tasks while it waits, so that waiting on a task
+  /// queued behind the current one does not deadlock.
+  pub fn wait(self) -> Option<Result<T, E>> {
+    loop {
+      match self.receiver.try_recv() {
+        Ok(result) => return Some(result),
+        Err(TryRecvError::Disconnected) => return None,
+        Err(TryRecvError::Empty) => {
+          if !worker::help_current_worker() {
+            return self.receiver.recv().ok();
+          }
+        }
+      }
+    }
+  }
+}
+
+impl<T, E> Debug for TaskHandle<T, E> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "TaskHandle")
+  }
+}
+
+/// Resolves to the result of the task once it settles. As with
+/// `TaskHandle::poll` returning `None`, the future fails with `Canceled` if
+/// the task was dropped by the runner before it settled.
+#[cfg(feature = "futures_support")]
+impl<T, E> Future for TaskHandle<T, E> {
+  type Item = Result<T, E>;
+  type Error = Canceled;
+
+  fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+    if let Some(result) = self.try_settle()? {
+      return Ok(Async::Ready(result));
+    }
+    // The result may have been sent before the task was stored, so check
+    // again now that the sending side will notify it
+    *self.waiter.lock().unwrap() = Some(task::current());
+    match self.try_settle()? {
+      Some(result) => Ok(Async::Ready(result)),
+      None => Ok(Async::NotReady),
+    }
+  }
+}
+
+#[cfg(feature = "futures_support")]
+impl<T, E> TaskHandle<T, E> {
+  fn try_settle(&self) -> Result<Option<Result<T, E>>, Canceled> {
+    match self.receiver.try_recv() {
+      Ok(result) => Ok(Some(result)),
+      Err(TryRecvError::Empty) => Ok(None),
+      Err(TryRecvError::Disconnected) => Err(Canceled),
+    }
+  }
+}
+
+/// The sending half of a `TaskHandle`.
+///
+/// Sending the result, or dropping the sender without sending one, notifies
+/// the task polling the handle as a future, if there is one.
+#[doc(hidden)]
+pub struct HandleSender<T, E> {
+  sender: Option<Sender<Result<T, E>>>,
+  #[cfg(feature = "futures_support")]
+  waiter: Waiter,
+}
+
+impl<T, E> HandleSender<T, E> {
+  pub fn send(self, result: Result<T, E>) {
+    if let Some(sender) = &self.sender {
+      let _ = sender.send(result);
+    }
+  }
+}
+
+impl<T, E> Drop for HandleSender<T, E> {
+  fn drop(&mut self) {
+    // The channel must be disconnected before notifying, so that a handle
+    // polled after a dropped task sees that it will never settle
+    self.sender.take();
+    #[cfg(feature = "futures_support")]
+    {
+      if let Some(task) = self.waiter.lock().unwrap().take() {
+        task.notify();
+      }
+    }
+  }
+}
+
+impl<T, E> Debug for HandleSender<T, E> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "HandleSender")
+  }
+}
@@ -1,44 +1,217 @@
-use std::sync::{Arc, RwLock};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use super::AllocPool;
 use super::Executable;
+use super::Job;
+use super::JobCounts;
+use super::Priority;
+use super::TaskBox;
+
+// Jobs are kept in a bucket per priority, ordered from high to low
+type Buckets = [VecDeque<Job>; 3];
 
 #[doc(hidden)]
 #[derive(Debug, Default, Clone)]
-pub struct TaskQueue(Arc<RwLock<Vec<Box<Executable>>>>);
+pub struct TaskQueue {
+  jobs: Arc<Mutex<Buckets>>,
+  job_counts: Arc<JobCounts>,
+  alloc_pool: Option<AllocPool>,
+}
 
 impl TaskQueue {
   pub fn new() -> Self {
     Self::default()
   }
 
-  pub fn append(&self, tasks: &mut Vec<Box<Executable>>) {
-    self.0.write().unwrap().append(tasks);
+  pub fn with_shared(job_counts: Arc<JobCounts>, alloc_pool: Option<AllocPool>) -> Self {
+    Self {
+      job_counts,
+      alloc_pool,
+      ..Self::default()
+    }
+  }
+
+  pub fn append(&self, jobs: &mut Vec<Job>) {
+    let mut buckets = self.jobs.lock().unwrap();
+    for job in jobs.drain(..) {
+      buckets[job.priority as usize].push_back(job);
+    }
+  }
+
+  pub fn insert<T>(&self, task: T)
+  where
+    T: Executable + 'static,
+  {
+    self.push(Job::new(self.boxed(task)));
+  }
+
+  pub fn insert_with_priority<T>(&self, task: T, priority: Priority)
+  where
+    T: Executable + 'static,
+  {
+    self.push(Job::with_priority(self.boxed(task), priority));
   }
 
-  pub fn insert(&self, task: Box<Executable>) {
-    self.0.write().unwrap().push(task);
+  pub fn try_insert<T>(&self, task: T, capacity: usize) -> Result<(), T>
+  where
+    T: Executable + 'static,
+  {
+    let mut buckets = self.jobs.lock().unwrap();
+    if len(&buckets) >= capacity {
+      return Err(task);
+    }
+    buckets[Priority::Normal as usize].push_back(Job::new(self.boxed(task)));
+    self.job_counts.insert();
+    Ok(())
   }
 
-  pub fn split(&self) -> Vec<Box<Executable>> {
-    let midpoint = {
-      let queue = self.0.read().unwrap();
-      if queue.len() < 2 {
-        return Vec::new();
+  fn push(&self, job: Job) {
+    let mut buckets = self.jobs.lock().unwrap();
+    buckets[job.priority as usize].push_back(job);
+    self.job_counts.insert();
+  }
+
+  fn boxed<T>(&self, task: T) -> TaskBox
+  where
+    T: Executable + 'static,
+  {
+    TaskBox::new(task, self.alloc_pool.as_ref())
+  }
+
+  /// Requeues a job that was taken with `next` and is still pending
+  pub fn requeue(&self, job: Job) {
+    let mut buckets = self.jobs.lock().unwrap();
+    buckets[job.priority as usize].push_back(job);
+    self.job_counts.finish_executing();
+  }
+
+  /// Splits off the lowest priority half of the queue
+  ///
+  /// The jobs are returned in priority order, so that appending them to
+  /// another queue preserves their ordering.
+  pub fn split(&self) -> Vec<Job> {
+    let mut buckets = self.jobs.lock().unwrap();
+    let len = len(&buckets);
+    if len < 2 {
+      return Vec::new();
+    }
+    let mut split_len = len - len / 2;
+    let mut split = Vec::with_capacity(split_len);
+    for bucket in buckets.iter_mut().rev() {
+      let midpoint = bucket.len().saturating_sub(split_len);
+      split_len -= bucket.len() - midpoint;
+      split.splice(0..0, bucket.split_off(midpoint));
+      if split_len == 0 {
+        break;
       }
-      queue.len() / 2
-    };
-    self.0.write().unwrap().split_off(midpoint)
+    }
+    split
   }
 
-  pub fn next(&self) -> Option<Box<Executable>> {
-    let mut queue = self.0.write().unwrap();
-    if queue.len() > 0 {
-      Some(queue.remove(0))
-    } else {
-      None
+  /// Takes the next job to execute, which is counted as executing until it
+  /// is requeued or settled
+  pub fn next(&self) -> Option<Job> {
+    let mut buckets = self.jobs.lock().unwrap();
+    let job = buckets.iter_mut().filter_map(|b| b.pop_front()).next();
+    if job.is_some() {
+      self.job_counts.start_executing();
     }
+    job
+  }
+
+  pub fn drain(&self) -> Vec<Job> {
+    let mut buckets = self.jobs.lock().unwrap();
+    buckets.iter_mut().flat_map(|b| b.drain(..)).collect()
+  }
+
+  pub fn clear(&self) {
+    let mut buckets = self.jobs.lock().unwrap();
+    self.job_counts.clear(len(&buckets));
+    for bucket in buckets.iter_mut() {
+      bucket.clear();
+    }
+  }
+
+  pub fn ptr_eq(&self, other: &TaskQueue) -> bool {
+    Arc::ptr_eq(&self.jobs, &other.jobs)
+  }
+
+  pub fn cost(&self) -> usize {
+    let buckets = self.jobs.lock().unwrap();
+    buckets.iter().flatten().fold(0, |m, j| m + j.task.estimated_cost())
   }
 
   pub fn len(&self) -> usize {
-    self.0.read().unwrap().len()
+    len(&self.jobs.lock().unwrap())
+  }
+}
+
+fn len(buckets: &Buckets) -> usize {
+  buckets.iter().fold(0, |m, b| m + b.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+  use super::*;
+  use super::super::super::task::{State, Task};
+
+  #[test]
+  fn drains_many_tasks_in_order() {
+    let task_queue = TaskQueue::new();
+    let started_at = Instant::now();
+    for i in 0..100_000 {
+      task_queue.insert(Box::new(Task::<(), ()>::new(|| State::Pending).named(i.to_string())));
+    }
+
+    let mut i = 0;
+    while let Some(job) = task_queue.next() {
+      assert_eq!(job.task.name(), Some(&*i.to_string()));
+      i += 1;
+    }
+    assert_eq!(i, 100_000);
+    assert!(started_at.elapsed() < Duration::from_secs(10));
+  }
+
+  #[test]
+  fn returns_higher_priority_tasks_first() {
+    let task_queue = TaskQueue::new();
+    for (name, priority) in &[
+      ("low", Priority::Low),
+      ("normal", Priority::Normal),
+      ("high", Priority::High),
+    ] {
+      task_queue.insert_with_priority(
+        Box::new(Task::<(), ()>::new(|| State::Pending).named(*name)),
+        *priority,
+      );
+    }
+
+    let names: Vec<_> = (0..3)
+      .map(|_| task_queue.next().unwrap().task.name().unwrap().to_string())
+      .collect();
+    assert_eq!(names, vec!["high", "normal", "low"]);
+  }
+
+  #[test]
+  fn splits_off_lowest_priority_half() {
+    let task_queue = TaskQueue::new();
+    for (i, priority) in [Priority::High, Priority::Low, Priority::Normal, Priority::Low]
+      .iter()
+      .enumerate()
+    {
+      task_queue.insert_with_priority(
+        Box::new(Task::<(), ()>::new(|| State::Pending).named(i.to_string())),
+        *priority,
+      );
+    }
+
+    let stolen = TaskQueue::new();
+    stolen.append(&mut task_queue.split());
+    assert_eq!(task_queue.len(), 2);
+    assert_eq!(task_queue.next().unwrap().priority, Priority::High);
+    assert_eq!(task_queue.next().unwrap().priority, Priority::Normal);
+    assert_eq!(stolen.next().unwrap().task.name(), Some("1"));
+    assert_eq!(stolen.next().unwrap().task.name(), Some("3"));
   }
 }
@@ -1,45 +1,539 @@
-use std::sync::{Arc, RwLock};
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 use rand::{thread_rng, Rng};
+use super::AllocPool;
 use super::Executable;
+use super::Hooks;
+use super::InFlight;
+use super::Job;
+use super::JobCounts;
+use super::LabelStats;
+use super::Priority;
 use super::TaskQueue;
 
+const IDLE_RECHECK_MS: u64 = 100;
+
 #[doc(hidden)]
-#[derive(Debug, Default, Clone)]
-pub struct TaskQueueSet(Arc<RwLock<Vec<TaskQueue>>>);
+#[derive(Debug, Clone)]
+pub struct TaskQueueSet {
+  task_queues: Arc<RwLock<Vec<TaskQueue>>>,
+  hooks: Arc<RwLock<Hooks>>,
+  label_stats: Arc<Mutex<HashMap<&'static str, LabelStats>>>,
+  capacity: Option<usize>,
+  is_aborted: Arc<AtomicBool>,
+  is_shut_down: Arc<AtomicBool>,
+  min_steal_len: usize,
+  in_flight: Option<Arc<Mutex<InFlight>>>,
+  idle: Arc<(Mutex<()>, Condvar)>,
+  thread_name_prefix: Option<Arc<str>>,
+  stack_size: Option<usize>,
+  thread_count: Arc<AtomicUsize>,
+  job_counts: Arc<JobCounts>,
+  alloc_pool: Option<AllocPool>,
+}
 
 impl TaskQueueSet {
   pub fn new() -> Self {
     Self::default()
   }
 
+  pub fn with_capacity(capacity: Option<usize>) -> Self {
+    Self {
+      capacity,
+      ..Self::default()
+    }
+  }
+
+  pub fn with_min_steal_len(mut self, min_steal_len: usize) -> Self {
+    self.min_steal_len = cmp::max(min_steal_len, 2);
+    self
+  }
+
+  pub fn with_in_flight_tracking(mut self) -> Self {
+    self.in_flight = Some(Default::default());
+    self
+  }
+
+  pub fn with_thread_config(
+    mut self,
+    name_prefix: Option<String>,
+    stack_size: Option<usize>,
+  ) -> Self {
+    self.thread_name_prefix = name_prefix.map(Arc::from);
+    self.stack_size = stack_size;
+    self
+  }
+
+  pub fn with_recycled_task_allocations(mut self) -> Self {
+    self.alloc_pool = Some(AllocPool::new());
+    self
+  }
+
+  /// Get a thread builder for spawning a new worker thread
+  ///
+  /// Worker threads are named with the configured prefix followed by a
+  /// number unique to each thread spawned for the set.
+  pub fn thread_builder(&self) -> thread::Builder {
+    let mut builder = thread::Builder::new();
+    if let Some(ref prefix) = self.thread_name_prefix {
+      let n = self.thread_count.fetch_add(1, Ordering::SeqCst);
+      builder = builder.name(format!("{}-{}", prefix, n));
+    }
+    if let Some(stack_size) = self.stack_size {
+      builder = builder.stack_size(stack_size);
+    }
+    builder
+  }
+
   pub fn new_queue(&self) -> TaskQueue {
-    let mut task_queues = self.0.write().unwrap();
-    let task_queue = TaskQueue::new();
+    let mut task_queues = self.task_queues.write().unwrap();
+    let task_queue = TaskQueue::with_shared(self.job_counts.clone(), self.alloc_pool.clone());
     task_queues.push(task_queue.clone());
     task_queue
   }
 
-  pub fn steal_from_rand_queue(&self) -> Vec<Box<Executable>> {
-    let task_queues = self.0.read().unwrap();
+  pub fn remove_queue(&self, task_queue: &TaskQueue) {
+    self
+      .task_queues
+      .write()
+      .unwrap()
+      .retain(|q| !q.ptr_eq(task_queue));
+  }
+
+  pub fn steal_from_rand_queue(&self) -> Vec<Job> {
+    let task_queues = self.task_queues.read().unwrap();
     let mut shuffled_task_queues: Vec<_> = task_queues.iter().collect();
     thread_rng().shuffle(&mut shuffled_task_queues);
 
-    match shuffled_task_queues.into_iter().find(|q| q.len() > 1) {
+    let min_steal_len = self.min_steal_len;
+    match shuffled_task_queues.into_iter().find(|q| q.len() >= min_steal_len) {
       Some(task_queue) => task_queue.split(),
       None => Vec::new(),
     }
   }
 
-  pub fn push_to_rand_queue(&self, task: Box<Executable>) {
-    let task_queues = self.0.read().unwrap();
-    let task_queue = thread_rng()
-      .choose(&task_queues)
-      .expect("No queues to push to");
-    task_queue.insert(task);
+  pub fn has_stealable_queue(&self) -> bool {
+    let task_queues = self.task_queues.read().unwrap();
+    task_queues.iter().any(|q| q.len() >= self.min_steal_len)
+  }
+
+  // Each push checks if the set is shut down while holding the queues' read
+  // lock. As `shut_down` takes the write lock, a task is either queued before
+  // the workers are told to stop, and so will be executed, or handed back.
+
+  pub fn push_to_rand_queue<T>(&self, task: T) -> Result<(), T>
+  where
+    T: Executable + 'static,
+  {
+    self.push_to_rand_queue_with_priority(task, Priority::Normal)
+  }
+
+  pub fn push_to_rand_queue_with_priority<T>(&self, task: T, priority: Priority) -> Result<(), T>
+  where
+    T: Executable + 'static,
+  {
+    {
+      let task_queues = self.task_queues.read().unwrap();
+      if self.is_shut_down() {
+        return Err(task);
+      }
+      let task_queue = thread_rng()
+        .choose(&task_queues)
+        .expect("No queues to push to");
+      task_queue.insert_with_priority(task, priority);
+    }
+    self.notify_one();
+    Ok(())
+  }
+
+  pub fn push_all_round_robin<T>(&self, tasks: Vec<T>) -> Result<(), Vec<T>>
+  where
+    T: Executable + 'static,
+  {
+    {
+      let task_queues = self.task_queues.read().unwrap();
+      if self.is_shut_down() {
+        return Err(tasks);
+      }
+      assert!(!task_queues.is_empty(), "No queues to push to");
+      let offset = thread_rng().gen_range(0, task_queues.len());
+      for (i, task) in tasks.into_iter().enumerate() {
+        task_queues[(offset + i) % task_queues.len()].insert(task);
+      }
+    }
+    self.notify_all();
+    Ok(())
+  }
+
+  pub fn push_to_shallowest_queue<T>(&self, task: T) -> Result<(), T>
+  where
+    T: Executable + 'static,
+  {
+    {
+      let task_queues = self.task_queues.read().unwrap();
+      if self.is_shut_down() {
+        return Err(task);
+      }
+      let task_queue = task_queues
+        .iter()
+        .min_by_key(|q| q.len())
+        .expect("No queues to push to");
+      task_queue.insert(task);
+    }
+    self.notify_one();
+    Ok(())
+  }
+
+  pub fn try_push_to_rand_queue<T>(&self, task: T) -> Result<(), T>
+  where
+    T: Executable + 'static,
+  {
+    let capacity = match self.capacity {
+      Some(capacity) => capacity,
+      None => return self.push_to_rand_queue(task),
+    };
+
+    let result = {
+      let task_queues = self.task_queues.read().unwrap();
+      if self.is_shut_down() {
+        return Err(task);
+      }
+      let mut shuffled_task_queues: Vec<_> = task_queues.iter().collect();
+      thread_rng().shuffle(&mut shuffled_task_queues);
+
+      let mut result = Err(task);
+      for task_queue in shuffled_task_queues {
+        match result {
+          Err(task) => result = task_queue.try_insert(task, capacity),
+          Ok(()) => break,
+        }
+      }
+      result
+    };
+    if result.is_ok() {
+      self.notify_one();
+    }
+    result
+  }
+
+  pub fn push_all_weighted<T>(&self, tasks: Vec<T>) -> Result<(), Vec<T>>
+  where
+    T: Executable + 'static,
+  {
+    {
+      let task_queues = self.task_queues.read().unwrap();
+      if self.is_shut_down() {
+        return Err(tasks);
+      }
+      assert!(!task_queues.is_empty(), "No queues to push to");
+      let mut costs: Vec<_> = task_queues.iter().map(|q| q.cost()).collect();
+
+      for task in tasks {
+        let index = (0..costs.len()).min_by_key(|&i| costs[i]).unwrap();
+        costs[index] += task.estimated_cost();
+        task_queues[index].insert(task);
+      }
+    }
+    self.notify_all();
+    Ok(())
+  }
+
+  // Jobs moved between queues have already been accepted, so unlike the
+  // pushes above, these do not check if the set is shut down
+
+  pub fn requeue_to_rand_queue(&self, job: Job) -> Result<(), Job> {
+    {
+      let task_queues = self.task_queues.read().unwrap();
+      match thread_rng().choose(&task_queues) {
+        Some(task_queue) => task_queue.requeue(job),
+        None => return Err(job),
+      }
+    }
+    self.notify_one();
+    Ok(())
+  }
+
+  pub fn requeue_all(&self, jobs: Vec<Job>) {
+    {
+      let task_queues = self.task_queues.read().unwrap();
+      assert!(!task_queues.is_empty(), "No queues to requeue to");
+      let offset = thread_rng().gen_range(0, task_queues.len());
+      let mut dealt: Vec<_> = task_queues.iter().map(|_| Vec::new()).collect();
+      for (i, job) in jobs.into_iter().enumerate() {
+        dealt[(offset + i) % task_queues.len()].push(job);
+      }
+      for (task_queue, jobs) in task_queues.iter().zip(dealt.iter_mut()) {
+        task_queue.append(jobs);
+      }
+    }
+    self.notify_all();
+  }
+
+  /// Blocks the calling worker until there is work it can take
+  ///
+  /// Returns immediately if the worker's own queue holds a task, another
+  /// queue is long enough to steal from, the worker is no longer running, or
+  /// the set has been aborted. As a pending task being requeued can make a
+  /// queue long enough to steal from without notifying, the wait is bounded
+  /// so that the worker checks again. Queue locks must not be held when
+  /// notifying waiting workers, as they are taken here while holding the
+  /// idle lock.
+  pub fn wait_for_work(&self, task_queue: &TaskQueue, is_running: &Mutex<bool>) {
+    let (ref lock, ref condvar) = *self.idle;
+    let guard = lock.lock().unwrap();
+    if task_queue.len() == 0
+      && !self.has_stealable_queue()
+      && *is_running.lock().unwrap()
+      && !self.is_aborted()
+    {
+      let _guard = condvar
+        .wait_timeout(guard, Duration::from_millis(IDLE_RECHECK_MS))
+        .unwrap();
+    }
+  }
+
+  pub fn notify_one(&self) {
+    let (ref lock, ref condvar) = *self.idle;
+    let _guard = lock.lock().unwrap();
+    condvar.notify_one();
+  }
+
+  pub fn notify_all(&self) {
+    let (ref lock, ref condvar) = *self.idle;
+    let _guard = lock.lock().unwrap();
+    condvar.notify_all();
+  }
+
+  /// Drops every task within the set's queues
+  ///
+  /// Tasks being executed by a worker at the time are not within a queue,
+  /// and so are unaffected.
+  pub fn clear(&self) {
+    let task_queues = self.task_queues.read().unwrap();
+    for task_queue in task_queues.iter() {
+      task_queue.clear();
+    }
+  }
+
+  pub fn abort(&self) {
+    self.is_aborted.store(true, Ordering::SeqCst);
+    self.notify_all();
+  }
+
+  pub fn is_aborted(&self) -> bool {
+    self.is_aborted.load(Ordering::SeqCst)
+  }
+
+  /// Mark the set as shutting down, after which no more tasks should be
+  /// submitted to it
+  pub fn shut_down(&self) {
+    let _task_queues = self.task_queues.write().unwrap();
+    self.is_shut_down.store(true, Ordering::SeqCst);
+  }
+
+  pub fn is_shut_down(&self) -> bool {
+    self.is_shut_down.load(Ordering::SeqCst)
+  }
+
+  pub fn in_flight(&self) -> Option<&Mutex<InFlight>> {
+    self.in_flight.as_deref()
+  }
+
+  pub fn hooks(&self) -> &RwLock<Hooks> {
+    &self.hooks
+  }
+
+  pub fn queue_depths(&self) -> Vec<usize> {
+    let task_queues = self.task_queues.read().unwrap();
+    task_queues.iter().map(|q| q.len()).collect()
+  }
+
+  pub fn label_stats(&self) -> &Mutex<HashMap<&'static str, LabelStats>> {
+    &self.label_stats
   }
 
   pub fn len(&self) -> usize {
-    let task_queues = self.0.read().unwrap();
+    let task_queues = self.task_queues.read().unwrap();
     task_queues.iter().fold(0, |m, q| m + q.len())
   }
+
+  /// Mark a job taken from one of the set's queues as settled
+  pub fn settle_job(&self) {
+    self.job_counts.settle();
+  }
+
+  /// Get the number of jobs that have been inserted and not yet settled or
+  /// cleared, whether queued, executing, or moving between queues
+  pub fn outstanding_len(&self) -> usize {
+    self.job_counts.outstanding_len()
+  }
+
+  /// Get the number of outstanding jobs that are not being executed
+  pub fn pending_len(&self) -> usize {
+    self.outstanding_len().saturating_sub(self.executing_len())
+  }
+
+  pub fn executing_len(&self) -> usize {
+    self.job_counts.executing_len()
+  }
+}
+
+impl Default for TaskQueueSet {
+  fn default() -> Self {
+    Self {
+      task_queues: Default::default(),
+      hooks: Default::default(),
+      label_stats: Default::default(),
+      capacity: None,
+      is_aborted: Default::default(),
+      is_shut_down: Default::default(),
+      min_steal_len: 2,
+      in_flight: None,
+      idle: Default::default(),
+      thread_name_prefix: None,
+      stack_size: None,
+      thread_count: Default::default(),
+      job_counts: Default::default(),
+      alloc_pool: None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::super::task::{State, Task};
+
+  #[test]
+  fn try_push_respects_capacity() {
+    let task_queue_set = TaskQueueSet::with_capacity(Some(1));
+    task_queue_set.new_queue();
+    task_queue_set.new_queue();
+
+    let rejected = (0..5)
+      .filter_map(|_| {
+        task_queue_set
+          .try_push_to_rand_queue(Task::<(), ()>::new(|| State::Pending))
+          .err()
+      })
+      .count();
+    assert_eq!(rejected, 3);
+    assert_eq!(task_queue_set.len(), 2);
+  }
+
+  #[test]
+  fn steals_only_from_queues_at_min_steal_len() {
+    let task_queue_set = TaskQueueSet::new().with_min_steal_len(4);
+    for depth in &[1, 2, 8] {
+      let task_queue = task_queue_set.new_queue();
+      for _ in 0..*depth {
+        task_queue.insert(Box::new(Task::<(), ()>::new(|| State::Pending)));
+      }
+    }
+
+    assert_eq!(task_queue_set.steal_from_rand_queue().len(), 4);
+    assert_eq!(task_queue_set.queue_depths(), vec![1, 2, 4]);
+  }
+
+  #[test]
+  fn push_all_round_robin_spreads_tasks_evenly() {
+    let task_queue_set = TaskQueueSet::new();
+    for _ in 0..3 {
+      task_queue_set.new_queue();
+    }
+    task_queue_set
+      .push_all_round_robin((0..10).map(|_| Task::<(), ()>::new(|| State::Pending)).collect())
+      .unwrap();
+
+    let mut depths = task_queue_set.queue_depths();
+    depths.sort();
+    assert_eq!(depths, vec![3, 3, 4]);
+  }
+
+  #[test]
+  fn wait_for_work_blocks_until_task_is_pushed() {
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    let task_queue_set = TaskQueueSet::new();
+    let task_queue = task_queue_set.new_queue();
+    let (sender, receiver) = channel();
+    let waiting_task_queue_set = task_queue_set.clone();
+    let waiter = thread::spawn(move || {
+      waiting_task_queue_set.wait_for_work(&task_queue, &Mutex::new(true));
+      sender.send(()).unwrap();
+    });
+
+    assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+    task_queue_set.push_to_rand_queue(Task::<(), ()>::new(|| State::Pending)).unwrap();
+    assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+    waiter.join().unwrap();
+  }
+
+  #[test]
+  fn wait_for_work_ignores_queues_too_short_to_steal_from() {
+    use std::time::{Duration, Instant};
+
+    let task_queue_set = TaskQueueSet::new().with_min_steal_len(2);
+    let own_task_queue = task_queue_set.new_queue();
+    let other_task_queue = task_queue_set.new_queue();
+    other_task_queue.insert(Box::new(Task::<(), ()>::new(|| State::Pending)));
+
+    let started_at = Instant::now();
+    task_queue_set.wait_for_work(&own_task_queue, &Mutex::new(true));
+    assert!(started_at.elapsed() >= Duration::from_millis(IDLE_RECHECK_MS));
+
+    other_task_queue.insert(Box::new(Task::<(), ()>::new(|| State::Pending)));
+    let started_at = Instant::now();
+    task_queue_set.wait_for_work(&own_task_queue, &Mutex::new(true));
+    assert!(started_at.elapsed() < Duration::from_millis(IDLE_RECHECK_MS));
+  }
+
+  #[test]
+  fn can_clear_queues() {
+    let task_queue_set = TaskQueueSet::new();
+    task_queue_set.new_queue();
+    task_queue_set.new_queue();
+    for _ in 0..5 {
+      task_queue_set.push_to_rand_queue(Task::<(), ()>::new(|| State::Pending)).unwrap();
+    }
+
+    assert_eq!(task_queue_set.len(), 5);
+    task_queue_set.clear();
+    assert_eq!(task_queue_set.len(), 0);
+  }
+
+  #[test]
+  fn push_all_weighted_balances_cost() {
+    #[derive(Debug)]
+    struct Costly(usize);
+
+    impl Executable for Costly {
+      fn exec(&mut self) -> bool {
+        true
+      }
+
+      fn estimated_cost(&self) -> usize {
+        self.0
+      }
+    }
+
+    let task_queue_set = TaskQueueSet::new();
+    let a = task_queue_set.new_queue();
+    let b = task_queue_set.new_queue();
+
+    let tasks = [10, 1, 1, 1, 1, 1, 1, 1, 1, 1]
+      .iter()
+      .map(|&cost| Costly(cost))
+      .collect();
+    task_queue_set.push_all_weighted(tasks).unwrap();
+
+    assert_eq!(a.cost() + b.cost(), 19);
+    assert!((a.cost() as isize - b.cost() as isize).abs() <= 1);
+  }
 }
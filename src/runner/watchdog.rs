@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use super::TaskQueueSet;
+
+const SAMPLE_INTERVAL_MS: u64 = 10;
+
+/// Warns when the tasks within a runner stop settling.
+///
+/// The watchdog periodically checks when a task last settled. If tasks are
+/// in flight but none have settled for `timeout`, the runner may be
+/// deadlocked, for example by tasks waiting upon each other. The watchdog
+/// then warns upon stderr with the names of the tasks in flight, and invokes
+/// the runner's deadlock callbacks. It warns once per stall.
+#[derive(Debug)]
+pub struct Watchdog {
+  is_running: Arc<Mutex<bool>>,
+  inner_handle: JoinHandle<()>,
+}
+
+impl Watchdog {
+  pub fn new(task_queue_set: TaskQueueSet, timeout: Duration) -> Self {
+    let is_running = Arc::new(Mutex::new(true));
+    let watchdog_is_running = is_running.clone();
+    let inner_handle = thread::spawn(move || {
+      let mut warned_stall: Option<Instant> = None;
+      while *watchdog_is_running.lock().unwrap() {
+        thread::sleep(Duration::from_millis(SAMPLE_INTERVAL_MS));
+
+        let names = {
+          let in_flight = match task_queue_set.in_flight() {
+            Some(in_flight) => in_flight.lock().unwrap(),
+            None => return,
+          };
+          let last_settled_at = in_flight.last_settled_at();
+          if in_flight.is_empty()
+            || last_settled_at.elapsed() < timeout
+            || warned_stall == Some(last_settled_at)
+          {
+            continue;
+          }
+          warned_stall = Some(last_settled_at);
+          in_flight.names()
+        };
+
+        eprintln!(
+          "task_kit: no task has settled for {:?}, possible deadlock between: {}",
+          timeout,
+          names.join(", ")
+        );
+        task_queue_set.hooks().read().unwrap().deadlock_suspected(&names);
+      }
+    });
+    Self {
+      is_running,
+      inner_handle,
+    }
+  }
+
+  pub fn finish(self) {
+    *self.is_running.lock().unwrap() = false;
+    self.inner_handle.join().unwrap();
+  }
+}
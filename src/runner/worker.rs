@@ -1,21 +1,35 @@
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, Mutex};
-use std::thread::{self, yield_now, JoinHandle};
-use super::{Executable, TaskQueue, TaskQueueSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{yield_now, JoinHandle};
+use std::time::Instant;
+use super::{Executable, Job, TaskQueue, TaskQueueSet};
 
 #[derive(Debug)]
 pub struct Worker {
   is_running: Arc<Mutex<bool>>,
+  is_retired: Arc<AtomicBool>,
   inner_handle: JoinHandle<()>,
+  task_queue_set: TaskQueueSet,
   task_queue: TaskQueue,
 }
 
 impl Worker {
   pub fn new(task_queue_set: TaskQueueSet) -> Self {
     let is_running = Arc::new(Mutex::new(true));
+    let is_retired = Arc::new(AtomicBool::new(false));
     let task_queue = task_queue_set.new_queue();
-    let inner_handle = WorkerInner::init(is_running.clone(), task_queue_set, task_queue.clone());
+    let inner_handle = WorkerInner::init(
+      is_running.clone(),
+      is_retired.clone(),
+      task_queue_set.clone(),
+      task_queue.clone(),
+    );
     Self {
       is_running,
+      is_retired,
+      task_queue_set,
       task_queue,
       inner_handle,
     }
@@ -25,17 +39,87 @@ impl Worker {
   where
     E: Executable + 'static,
   {
-    self.task_queue.insert(Box::new(task));
+    self.task_queue.insert(task);
+    self.task_queue_set.notify_all();
   }
 
-  pub fn finish(self) {
+  pub fn stop(&self) {
     *self.is_running.lock().unwrap() = false;
+    self.task_queue_set.notify_all();
+  }
+
+  // Stop the worker, having it hand off any pending task it is executing to
+  // the set rather than keeping it within its own queue
+  fn retire(&self) {
+    self.is_retired.store(true, Ordering::SeqCst);
+    self.stop();
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.inner_handle.is_finished()
+  }
+
+  pub fn join(self) {
     self.inner_handle.join().unwrap();
   }
+
+  pub fn finish(self) {
+    self.stop();
+    self.join();
+  }
+}
+
+/// Grows or shrinks a set of workers to a given count
+///
+/// Removed workers are told to stop and returned, so that they can be
+/// joined without holding the lock on the set of workers. The tasks queued
+/// for a removed worker are moved to the queues of the remaining workers,
+/// and a task it was executing is handed off too if still pending. If no
+/// workers remain, the removed workers finish their own tasks instead.
+pub fn resize(workers: &mut Vec<Worker>, task_queue_set: &TaskQueueSet, n: usize) -> Vec<Worker> {
+  while workers.len() < n {
+    workers.push(Worker::new(task_queue_set.clone()));
+  }
+  let mut removed = Vec::new();
+  while workers.len() > n {
+    let worker = workers.pop().unwrap();
+    task_queue_set.remove_queue(&worker.task_queue);
+    if workers.is_empty() {
+      worker.stop();
+    } else {
+      worker.retire();
+      task_queue_set.requeue_all(worker.task_queue.drain());
+    }
+    removed.push(worker);
+  }
+  removed
+}
+
+thread_local! {
+  static CURRENT_WORKER: RefCell<Option<WorkerInner>> = const { RefCell::new(None) };
+}
+
+/// Executes a queued task if called from within a worker thread
+///
+/// Returns false if the calling thread is not a worker. This allows a task
+/// blocking on the result of another task to keep its worker's queue moving,
+/// rather than deadlocking when the other task is queued behind it.
+pub fn help_current_worker() -> bool {
+  CURRENT_WORKER.with(|current_worker| match *current_worker.borrow() {
+    Some(ref worker_inner) => {
+      if !worker_inner.step() && !worker_inner.try_steal() {
+        yield_now();
+      }
+      true
+    }
+    None => false,
+  })
 }
 
+#[derive(Clone)]
 pub struct WorkerInner {
   is_running: Arc<Mutex<bool>>,
+  is_retired: Arc<AtomicBool>,
   task_queue_set: TaskQueueSet,
   task_queue: TaskQueue,
 }
@@ -43,21 +127,31 @@ pub struct WorkerInner {
 impl WorkerInner {
   fn init(
     is_running: Arc<Mutex<bool>>,
+    is_retired: Arc<AtomicBool>,
     task_queue_set: TaskQueueSet,
     task_queue: TaskQueue,
   ) -> JoinHandle<()> {
-    thread::spawn(move || {
-      WorkerInner::new(is_running, task_queue_set, task_queue).run();
-    })
+    task_queue_set
+      .thread_builder()
+      .spawn(move || {
+        let worker_inner = WorkerInner::new(is_running, is_retired, task_queue_set, task_queue);
+        CURRENT_WORKER.with(|current_worker| {
+          *current_worker.borrow_mut() = Some(worker_inner.clone());
+        });
+        worker_inner.run();
+      })
+      .expect("Failed to spawn worker thread")
   }
 
   fn new(
     is_running: Arc<Mutex<bool>>,
+    is_retired: Arc<AtomicBool>,
     task_queue_set: TaskQueueSet,
     task_queue: TaskQueue,
   ) -> Self {
     Self {
       is_running,
+      is_retired,
       task_queue_set,
       task_queue,
     }
@@ -65,18 +159,93 @@ impl WorkerInner {
 
   fn run(&self) {
     loop {
-      match self.task_queue.next() {
-        Some(mut task) => loop {
-          if task.exec() {
-            break;
-          }
-        },
-        None => if !self.try_steal() {
-          if !*self.is_running.lock().unwrap() {
-            break;
-          }
-          yield_now();
-        },
+      if self.task_queue_set.is_aborted() {
+        break;
+      }
+      if self.step() {
+        continue;
+      }
+      if !*self.is_running.lock().unwrap() {
+        break;
+      }
+      if !self.try_steal() {
+        self.task_queue_set.wait_for_work(&self.task_queue, &self.is_running);
+      }
+    }
+  }
+
+  fn step(&self) -> bool {
+    match self.task_queue.next() {
+      Some(job) => {
+        self.exec(job);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn exec(&self, mut job: Job) {
+    let started_at = match job.started_at {
+      Some(started_at) => started_at,
+      None => {
+        let started_at = Instant::now();
+        job.started_at = Some(started_at);
+        self.task_queue_set.hooks().read().unwrap().task_start(job.task.name());
+        if let Some(in_flight) = self.task_queue_set.in_flight() {
+          in_flight.lock().unwrap().start(job.task.name());
+        }
+        if let Some(label) = job.task.label() {
+          let mut label_stats = self.task_queue_set.label_stats().lock().unwrap();
+          label_stats.entry(label).or_default().in_flight += 1;
+        }
+        started_at
+      }
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| job.task.exec())) {
+      Ok(true) => {
+        self.settle(&job, true);
+        self
+          .task_queue_set
+          .hooks()
+          .read()
+          .unwrap()
+          .task_complete(job.task.name(), started_at.elapsed());
+        self.task_queue_set.settle_job();
+      }
+      Ok(false) => self.requeue(job),
+      Err(_) => {
+        self.settle(&job, false);
+        self
+          .task_queue_set
+          .hooks()
+          .read()
+          .unwrap()
+          .task_panic(job.task.name(), started_at.elapsed());
+        self.task_queue_set.settle_job();
+      }
+    }
+  }
+
+  fn requeue(&self, job: Job) {
+    if !self.is_retired.load(Ordering::SeqCst) {
+      return self.task_queue.requeue(job);
+    }
+    if let Err(job) = self.task_queue_set.requeue_to_rand_queue(job) {
+      self.task_queue.requeue(job);
+    }
+  }
+
+  fn settle(&self, job: &Job, completed: bool) {
+    if let Some(in_flight) = self.task_queue_set.in_flight() {
+      in_flight.lock().unwrap().settle(job.task.name());
+    }
+    if let Some(label) = job.task.label() {
+      let mut label_stats = self.task_queue_set.label_stats().lock().unwrap();
+      let stats = label_stats.entry(label).or_default();
+      stats.in_flight -= 1;
+      if completed {
+        stats.executed += 1;
       }
     }
   }
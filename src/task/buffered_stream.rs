@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use futures::{task, Async, Poll, Stream};
+use super::Task;
+
+/// A stream of the results of a collection of tasks.
+///
+/// At most a fixed number of tasks are executed at once. Results are
+/// yielded in the order the tasks settle, and as each task settles the next
+/// waiting task is started, so a slow consumer holds back the execution of
+/// the remaining tasks.
+///
+/// Buffered streams are created with `Task::into_buffered_stream`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate futures;
+/// # extern crate task_kit;
+/// # use futures::Stream;
+/// # use task_kit::prelude::*;
+/// # fn main() {
+/// let tasks: Vec<Task<u32, ()>> = (0..4).map(|i| Task::with(move || i)).collect();
+/// let mut values: Vec<_> = Task::into_buffered_stream(tasks, 2)
+///   .wait()
+///   .collect::<Result<_, _>>()
+///   .unwrap();
+/// values.sort();
+/// assert_eq!(values, vec![0, 1, 2, 3]);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BufferedStream<'a, T, E> {
+  waiting: VecDeque<Task<'a, T, E>>,
+  in_flight: Vec<Task<'a, T, E>>,
+  buffer: usize,
+}
+
+impl<'a, T, E> BufferedStream<'a, T, E>
+where
+  T: Send + 'a,
+  E: Send + 'a,
+{
+  #[doc(hidden)]
+  pub fn new(tasks: Vec<Task<'a, T, E>>, buffer: usize) -> Self {
+    assert!(buffer > 0, "BufferedStream requires a buffer of at least one task");
+    BufferedStream {
+      waiting: tasks.into_iter().collect(),
+      in_flight: Vec::with_capacity(buffer),
+      buffer,
+    }
+  }
+}
+
+impl<'a, T, E> Stream for BufferedStream<'a, T, E>
+where
+  T: Send + 'a,
+  E: Send + 'a,
+{
+  type Item = T;
+  type Error = E;
+
+  // Tasks have no way to signal when they can make progress, so the current
+  // task is notified straight away to have the executor poll the stream
+  // again.
+  fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+    while self.in_flight.len() < self.buffer {
+      match self.waiting.pop_front() {
+        Some(task) => self.in_flight.push(task),
+        None => break,
+      }
+    }
+    if self.in_flight.is_empty() {
+      return Ok(Async::Ready(None));
+    }
+
+    for i in 0..self.in_flight.len() {
+      if let Some(result) = self.in_flight[i].poll() {
+        self.in_flight.swap_remove(i);
+        return result.map(|v| Async::Ready(Some(v)));
+      }
+    }
+    task::current().notify();
+    Ok(Async::NotReady)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn can_bound_tasks_in_flight() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use super::super::State;
+
+    let active = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<Task<usize, ()>> = (0..50)
+      .map(|i| {
+        let active = active.clone();
+        let peak = peak.clone();
+        let mut polls = 0;
+        Task::new(move || {
+          if polls == 0 {
+            let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now_active, Ordering::SeqCst);
+          }
+          polls += 1;
+          if polls < 3 + i % 4 {
+            return State::Pending;
+          }
+          active.fetch_sub(1, Ordering::SeqCst);
+          State::Resolve(i)
+        })
+      })
+      .collect();
+
+    let mut values: Vec<_> = Task::into_buffered_stream(tasks, 8)
+      .wait()
+      .collect::<Result<_, _>>()
+      .unwrap();
+    values.sort();
+
+    assert_eq!(peak.load(Ordering::SeqCst), 8);
+    assert_eq!(values, (0..50).collect::<Vec<_>>());
+  }
+}
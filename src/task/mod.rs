@@ -1,5 +1,17 @@
+#[cfg(feature = "futures_support")]
+mod buffered_stream;
+mod rate_limiter;
+mod semaphore;
 mod state;
+mod state_watch;
 mod task;
+mod timeout_error;
 
+#[cfg(feature = "futures_support")]
+pub use self::buffered_stream::BufferedStream;
+pub use self::rate_limiter::RateLimiter;
+pub use self::semaphore::{Permit, Semaphore};
 pub use self::state::State;
+pub use self::state_watch::{StateKind, StateWatch};
 pub use self::task::Task;
+pub use self::timeout_error::TimeoutError;
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A rate limiter for capping how often a class of tasks may be executed.
+///
+/// Rate limiters can be cloned and shared between tasks. Each clone draws
+/// from the same rate, so tasks spread across the runner's workers respect
+/// one combined limit. Tokens are handed out evenly spaced over time rather
+/// than in bursts.
+///
+/// # Examples
+///
+/// ```
+/// # use task_kit::prelude::*;
+/// # use task_kit::task::RateLimiter;
+/// # let mut runner = Runner::new();
+/// let limiter = RateLimiter::new(100);
+/// for _ in 0..10 {
+///   let task: Task<(), ()> = Task::new(|| Resolve(())).rate_limited(&limiter);
+///   runner.run(task);
+/// }
+/// # runner.finish();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+  started_at: Instant,
+  interval: u64,
+  next_at: Arc<AtomicU64>,
+}
+
+impl RateLimiter {
+  /// Create a new rate limiter allowing a given number of tokens per second
+  pub fn new(per_second: u32) -> Self {
+    assert!(per_second > 0, "RateLimiter requires a rate of at least one per second");
+    RateLimiter {
+      started_at: Instant::now(),
+      interval: (Duration::from_secs(1) / per_second).as_nanos() as u64,
+      next_at: Arc::new(AtomicU64::new(0)),
+    }
+  }
+
+  /// Attempt to take a token without blocking
+  ///
+  /// Returns `false` if the next token is not yet available.
+  pub fn try_acquire(&self) -> bool {
+    self.try_acquire_at(self.started_at.elapsed().as_nanos() as u64)
+  }
+
+  // Attempt to take a token at a given number of nanoseconds since the
+  // limiter was created
+  fn try_acquire_at(&self, now: u64) -> bool {
+    let mut next_at = self.next_at.load(Ordering::SeqCst);
+    loop {
+      if next_at > now {
+        return false;
+      }
+      match self.next_at.compare_exchange(
+        next_at,
+        now + self.interval,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+      ) {
+        Ok(_) => return true,
+        Err(current) => next_at = current,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn can_acquire_tokens_at_rate() {
+    let limiter = RateLimiter::new(50);
+    let shared = limiter.clone();
+    let interval = Duration::from_millis(20).as_nanos() as u64;
+    assert!(limiter.try_acquire_at(0));
+    assert!(!shared.try_acquire_at(0));
+    assert!(!shared.try_acquire_at(interval - 1));
+    assert!(shared.try_acquire_at(interval));
+    assert!(!limiter.try_acquire_at(interval));
+  }
+
+  #[test]
+  fn does_not_accumulate_unused_tokens() {
+    let limiter = RateLimiter::new(50);
+    let interval = Duration::from_millis(20).as_nanos() as u64;
+    assert!(limiter.try_acquire_at(interval * 10));
+    assert!(!limiter.try_acquire_at(interval * 10));
+    assert!(!limiter.try_acquire_at(interval * 11 - 1));
+  }
+}
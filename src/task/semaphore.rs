@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A counting semaphore for limiting the concurrency of a class of tasks.
+///
+/// Semaphores can be cloned and shared between tasks. Each clone draws from
+/// the same pool of permits.
+///
+/// Acquiring a permit never blocks. A task waiting on a permit, such as one
+/// created with `Task::with_permit`, stays pending and tries again each time
+/// it is executed, so it spins through the runner's queues rather than
+/// parking on a condition variable. This keeps workers free to execute other
+/// tasks, at the cost of polling while permits are scarce, and means that
+/// waiting tasks are not granted permits in any particular order.
+///
+/// # Examples
+///
+/// ```
+/// # use task_kit::prelude::*;
+/// # use task_kit::task::Semaphore;
+/// # let mut runner = Runner::new();
+/// let semaphore = Semaphore::new(4);
+/// for _ in 0..10 {
+///   let task: Task<(), ()> = Task::with_permit(&semaphore, || Resolve(()));
+///   runner.run(task);
+/// }
+/// # runner.finish();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Semaphore(Arc<AtomicUsize>);
+
+impl Semaphore {
+  /// Create a new semaphore with a given number of permits
+  pub fn new(permits: usize) -> Self {
+    Semaphore(Arc::new(AtomicUsize::new(permits)))
+  }
+
+  /// Attempt to acquire a permit without blocking
+  ///
+  /// Returns `None` if no permits are available. The permit is returned to
+  /// the semaphore when it is dropped.
+  pub fn try_acquire(&self) -> Option<Permit> {
+    let mut available = self.0.load(Ordering::SeqCst);
+    loop {
+      if available == 0 {
+        return None;
+      }
+      match self.0.compare_exchange(
+        available,
+        available - 1,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+      ) {
+        Ok(_) => return Some(Permit(self.clone())),
+        Err(current) => available = current,
+      }
+    }
+  }
+
+  /// Get the number of permits currently available
+  pub fn available(&self) -> usize {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+/// A permit acquired from a `Semaphore`
+///
+/// The permit is released back to the semaphore when dropped.
+#[derive(Debug)]
+pub struct Permit(Semaphore);
+
+impl Drop for Permit {
+  fn drop(&mut self) {
+    (self.0).0.fetch_add(1, Ordering::SeqCst);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn can_acquire_and_release_permits() {
+    let semaphore = Semaphore::new(2);
+    let a = semaphore.try_acquire();
+    let b = semaphore.try_acquire();
+    assert!(a.is_some() && b.is_some());
+    assert!(semaphore.try_acquire().is_none());
+    drop(a);
+    assert_eq!(semaphore.available(), 1);
+    assert!(semaphore.try_acquire().is_some());
+  }
+}
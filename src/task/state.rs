@@ -1,7 +1,17 @@
-use std::fmt::{self, Debug};
+use std::fmt::{self, Debug, Display};
 use std::mem;
+use std::ops::{Add, Mul, Sub};
 
+#[cfg(feature = "try_trait")]
+use std::convert::Infallible;
+#[cfg(feature = "try_trait")]
+use std::ops::{ControlFlow, FromResidual, Residual, Try};
+
+// Each variant is serialized with its name as a tag, so the drained
+// `Resolved` and `Rejected` markers round trip as distinct states.
 #[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "state", content = "value"))]
 pub enum State<T = (), E = ()> {
   Pending,
   Resolve(T),
@@ -46,6 +56,18 @@ impl<T, E> State<T, E> {
     false
   }
 
+  /// Returns true if the state is `State::Resolve`. This is the same as
+  /// `is_resolve`, named to match `Result::is_ok`.
+  pub fn is_ok(&self) -> bool {
+    self.is_resolve()
+  }
+
+  /// Returns true if the state is `State::Reject`. This is the same as
+  /// `is_reject`, named to match `Result::is_err`.
+  pub fn is_err(&self) -> bool {
+    self.is_reject()
+  }
+
   pub fn resolve(self) -> Option<T> {
     if let State::Resolve(r) = self {
       Some(r)
@@ -62,6 +84,42 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Counts the states within a slice, returning the number that are
+  /// `(pending, resolved, rejected)`.
+  ///
+  /// Both `State::Resolve` and `State::Resolved` count as resolved, and both
+  /// `State::Reject` and `State::Rejected` count as rejected.
+  pub fn count_settled(states: &[State<T, E>]) -> (usize, usize, usize) {
+    states.iter().fold((0, 0, 0), |(p, res, rej), state| match *state {
+      State::Pending => (p + 1, res, rej),
+      State::Resolve(_) | State::Resolved => (p, res + 1, rej),
+      State::Reject(_) | State::Rejected => (p, res, rej + 1),
+    })
+  }
+
+  /// Folds the resolved values of a collection of states into a single
+  /// state.
+  ///
+  /// Rejects with the first `State::Reject` found. Otherwise, if any state
+  /// is not `State::Resolve`, the result is `State::Pending`.
+  pub fn try_fold<A, F>(states: Vec<State<T, E>>, init: A, mut f: F) -> State<A, E>
+  where
+    F: FnMut(A, T) -> A,
+  {
+    let mut acc = Some(init);
+    for state in states {
+      match state {
+        State::Resolve(r) => acc = acc.map(|a| f(a, r)),
+        State::Reject(e) => return State::Reject(e),
+        _ => acc = None,
+      }
+    }
+    match acc {
+      Some(a) => State::Resolve(a),
+      None => State::Pending,
+    }
+  }
+
   pub fn as_ref(&self) -> State<&T, &E> {
     match self {
       &State::Pending => State::Pending,
@@ -82,6 +140,30 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Converts an option into a state, rejecting with `err` if it is `None`
+  pub fn from_option(opt: Option<T>, err: E) -> State<T, E> {
+    match opt {
+      Some(r) => State::Resolve(r),
+      None => State::Reject(err),
+    }
+  }
+
+  /// Converts the state into an option, which is `Some` only for
+  /// `State::Resolve`. This is the same as `resolve`.
+  pub fn into_option(self) -> Option<T> {
+    self.resolve()
+  }
+
+  /// Converts a result into a settled state
+  ///
+  /// `Ok` becomes `State::Resolve` and `Err` becomes `State::Reject`.
+  pub fn from_result(res: Result<T, E>) -> Self {
+    match res {
+      Ok(r) => State::Resolve(r),
+      Err(e) => State::Reject(e),
+    }
+  }
+
   pub fn into_result(self) -> Option<Result<T, E>> {
     match self {
       State::Pending => None,
@@ -92,6 +174,42 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Converts a settled state into a result, or gives the state back if it
+  /// is pending or has already been taken
+  pub fn settled_result(self) -> Result<Result<T, E>, Self> {
+    match self {
+      State::Resolve(r) => Ok(Ok(r)),
+      State::Reject(e) => Ok(Err(e)),
+      state => Err(state),
+    }
+  }
+
+  /// Takes the value or error out of a settled state, leaving behind
+  /// `Resolved` or `Rejected`
+  ///
+  /// Returns `None` if the state is pending or has already been taken.
+  pub fn take_result(&mut self) -> Option<Result<T, E>> {
+    self.take().into_result()
+  }
+
+  /// Describes the state in words, including its value or error
+  ///
+  /// Intended for logs, where the `Debug` output of a state hides the
+  /// payload.
+  pub fn describe(&self) -> String
+  where
+    T: Display,
+    E: Display,
+  {
+    match *self {
+      State::Pending => "pending".to_string(),
+      State::Resolve(ref r) => format!("resolved with {}", r),
+      State::Resolved => "resolved, value already taken".to_string(),
+      State::Reject(ref e) => format!("rejected with {}", e),
+      State::Rejected => "rejected, error already taken".to_string(),
+    }
+  }
+
   pub fn map<U, F>(self, op: F) -> State<U, E>
   where
     F: FnOnce(T) -> U,
@@ -105,6 +223,44 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Like `map`, but for transformations that may fail
+  ///
+  /// An `Ok` returned by `op` resolves with its value, while an `Err`
+  /// rejects with its error. Other states are passed through.
+  pub fn try_map<U, F>(self, op: F) -> State<U, E>
+  where
+    F: FnOnce(T) -> Result<U, E>,
+  {
+    match self {
+      State::Pending => State::Pending,
+      State::Resolve(r) => match op(r) {
+        Ok(u) => State::Resolve(u),
+        Err(e) => State::Reject(e),
+      },
+      State::Resolved => State::Resolved,
+      State::Reject(e) => State::Reject(e),
+      State::Rejected => State::Rejected,
+    }
+  }
+
+  /// Validates a resolved value, rejecting with the error returned by `op`
+  /// if the check fails
+  ///
+  /// The value is kept if `op` returns `Ok`. Other states are passed
+  /// through.
+  pub fn ensure<F>(self, op: F) -> State<T, E>
+  where
+    F: FnOnce(&T) -> Result<(), E>,
+  {
+    match self {
+      State::Resolve(r) => match op(&r) {
+        Ok(()) => State::Resolve(r),
+        Err(e) => State::Reject(e),
+      },
+      state => state,
+    }
+  }
+
   pub fn map_err<F, O>(self, op: F) -> State<T, O>
   where
     F: FnOnce(E) -> O,
@@ -118,6 +274,25 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Applies `f` to a resolved value or `default` to a rejected error
+  ///
+  /// # Panics
+  ///
+  /// Panics if the state is not `Resolve` or `Reject`.
+  pub fn map_or_else<U, D, F>(self, default: D, f: F) -> U
+  where
+    D: FnOnce(E) -> U,
+    F: FnOnce(T) -> U,
+  {
+    match self {
+      State::Pending => panic!("called `State::map_or_else()` on a `State::Pending` value"),
+      State::Resolve(r) => f(r),
+      State::Resolved => panic!("called `State::map_or_else()` on a `State::Resolved` value"),
+      State::Reject(e) => default(e),
+      State::Rejected => panic!("called `State::map_or_else()` on a `State::Rejected` value"),
+    }
+  }
+
   pub fn and<U>(self, res: State<U, E>) -> State<U, E> {
     if let State::Reject(e) = self {
       return State::Reject(e);
@@ -125,6 +300,19 @@ impl<T, E> State<T, E> {
     res
   }
 
+  /// Swaps the resolve and reject channels of the state, so that
+  /// `State::Resolve` becomes `State::Reject` and `State::Resolved` becomes
+  /// `State::Rejected`, and vice versa.
+  pub fn transpose_channels(self) -> State<E, T> {
+    match self {
+      State::Pending => State::Pending,
+      State::Resolve(r) => State::Reject(r),
+      State::Resolved => State::Rejected,
+      State::Reject(e) => State::Resolve(e),
+      State::Rejected => State::Resolved,
+    }
+  }
+
   pub fn and_then<U, F>(self, op: F) -> State<U, E>
   where
     F: FnOnce(T) -> State<U, E>,
@@ -138,6 +326,27 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Like `and_then`, but for continuations that return a `Result`. This is
+  /// the same as `try_map`.
+  pub fn and_then_result<U, F>(self, op: F) -> State<U, E>
+  where
+    F: FnOnce(T) -> Result<U, E>,
+  {
+    self.try_map(op)
+  }
+
+  /// Like `and_then`, but borrows the resolved value rather than consuming
+  /// the state. Returns `None` unless the state is `State::Resolve`.
+  pub fn and_then_peek<U, F>(&self, op: F) -> Option<State<U, E>>
+  where
+    F: FnOnce(&T) -> State<U, E>,
+  {
+    match *self {
+      State::Resolve(ref r) => Some(op(r)),
+      _ => None,
+    }
+  }
+
   pub fn or<O>(self, res: State<T, O>) -> State<T, O> {
     if let State::Resolve(r) = self {
       return State::Resolve(r);
@@ -158,6 +367,20 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Returns whichever state is resolved if exactly one of the two is
+  ///
+  /// If neither is resolved then the first rejection is returned, checking
+  /// `self` before `res`. If both are resolved, or neither is resolved nor
+  /// rejected, the result is pending.
+  pub fn xor(self, res: State<T, E>) -> State<T, E> {
+    match (self, res) {
+      (State::Resolve(_), State::Resolve(_)) => State::Pending,
+      (State::Resolve(r), _) | (_, State::Resolve(r)) => State::Resolve(r),
+      (State::Reject(e), _) | (_, State::Reject(e)) => State::Reject(e),
+      _ => State::Pending,
+    }
+  }
+
   pub fn unwrap_or(self, or: T) -> T {
     if let State::Resolve(r) = self {
       r
@@ -177,6 +400,18 @@ impl<T, E> State<T, E> {
     }
   }
 
+  /// Sets the state to `Resolve(val)` if it is not already resolved, then
+  /// returns a mutable reference to the resolved value
+  pub fn get_or_insert(&mut self, val: T) -> &mut T {
+    if !self.is_resolve() {
+      *self = State::Resolve(val);
+    }
+    match *self {
+      State::Resolve(ref mut r) => r,
+      _ => unreachable!(),
+    }
+  }
+
   pub fn take(&mut self) -> State<T, E> {
     match self {
       &mut State::Pending => State::Pending,
@@ -255,6 +490,35 @@ where
   }
 }
 
+impl<T, E> State<T, E>
+where
+  T: Ord,
+{
+  /// Resolves the larger of two resolved values
+  ///
+  /// A rejection from either state takes precedence, checking `self` first.
+  /// If either state is otherwise unresolved the result is pending.
+  pub fn max(self, res: State<T, E>) -> State<T, E> {
+    match (self, res) {
+      (State::Reject(e), _) | (_, State::Reject(e)) => State::Reject(e),
+      (State::Resolve(a), State::Resolve(b)) => State::Resolve(a.max(b)),
+      _ => State::Pending,
+    }
+  }
+
+  /// Resolves the smaller of two resolved values
+  ///
+  /// A rejection from either state takes precedence, checking `self` first.
+  /// If either state is otherwise unresolved the result is pending.
+  pub fn min(self, res: State<T, E>) -> State<T, E> {
+    match (self, res) {
+      (State::Reject(e), _) | (_, State::Reject(e)) => State::Reject(e),
+      (State::Resolve(a), State::Resolve(b)) => State::Resolve(a.min(b)),
+      _ => State::Pending,
+    }
+  }
+}
+
 impl<T, E> State<T, E>
 where
   T: Clone,
@@ -281,6 +545,36 @@ where
   }
 }
 
+impl<'a, T, E> State<&'a T, &'a E>
+where
+  T: Clone,
+  E: Clone,
+{
+  pub fn cloned(self) -> State<T, E> {
+    match self {
+      State::Pending => State::Pending,
+      State::Resolve(r) => State::Resolve(r.clone()),
+      State::Resolved => State::Resolved,
+      State::Reject(e) => State::Reject(e.clone()),
+      State::Rejected => State::Rejected,
+    }
+  }
+}
+
+impl<'a, T, E> State<&'a T, &'a E>
+where
+  T: Clone,
+{
+  /// Returns a clone of the resolved value, or `default` if the state is not
+  /// `State::Resolve`.
+  pub fn resolved_cloned_or(self, default: T) -> T {
+    match self {
+      State::Resolve(r) => r.clone(),
+      _ => default,
+    }
+  }
+}
+
 impl<T, E> Debug for State<T, E> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
@@ -293,8 +587,495 @@ impl<T, E> Debug for State<T, E> {
   }
 }
 
+// Unlike `Debug`, which hides the value or error, `Display` includes it
+impl<T, E> Display for State<T, E>
+where
+  T: Display,
+  E: Display,
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      State::Pending => write!(f, "Pending"),
+      State::Resolve(ref r) => write!(f, "Resolve({})", r),
+      State::Resolved => write!(f, "Resolved"),
+      State::Reject(ref e) => write!(f, "Reject({})", e),
+      State::Rejected => write!(f, "Rejected"),
+    }
+  }
+}
+
 impl<T, E> From<T> for State<T, E> {
   fn from(val: T) -> Self {
     State::Resolve(val)
   }
 }
+
+impl<T, E> From<Result<T, E>> for State<T, E> {
+  fn from(res: Result<T, E>) -> Self {
+    State::from_result(res)
+  }
+}
+
+/// Implements an arithmetic operator for states.
+///
+/// A rejection from either operand takes precedence, with the left operand
+/// checked first. If both operands are resolved then the operator is applied
+/// to their values. In all other cases the resulting state is pending.
+macro_rules! impl_state_op {
+  ($op:ident, $method:ident) => {
+    impl<T, U, E> $op<State<U, E>> for State<T, E>
+    where
+      T: $op<U>,
+    {
+      type Output = State<T::Output, E>;
+
+      fn $method(self, other: State<U, E>) -> Self::Output {
+        match (self, other) {
+          (State::Reject(e), _) | (_, State::Reject(e)) => State::Reject(e),
+          (State::Resolve(a), State::Resolve(b)) => State::Resolve(a.$method(b)),
+          _ => State::Pending,
+        }
+      }
+    }
+  };
+}
+
+impl_state_op!(Add, add);
+impl_state_op!(Sub, sub);
+impl_state_op!(Mul, mul);
+
+/// Allows `?` to be used on states within closures returning a state
+///
+/// `State::Resolve` continues with the resolved value. `State::Reject`
+/// returns early with the error, converted with `From`, and `State::Pending`
+/// returns early with `State::Pending`, so the closure is retried on its next
+/// execution. Using `?` on `State::Resolved` or `State::Rejected` panics, as
+/// their values have already been taken.
+#[cfg(feature = "try_trait")]
+impl<T, E> Try for State<T, E> {
+  type Output = T;
+  type Residual = State<Infallible, E>;
+
+  fn from_output(output: T) -> Self {
+    State::Resolve(output)
+  }
+
+  fn branch(self) -> ControlFlow<Self::Residual, T> {
+    match self {
+      State::Pending => ControlFlow::Break(State::Pending),
+      State::Resolve(r) => ControlFlow::Continue(r),
+      State::Reject(e) => ControlFlow::Break(State::Reject(e)),
+      State::Resolved => panic!("called `?` on a `State::Resolved` value"),
+      State::Rejected => panic!("called `?` on a `State::Rejected` value"),
+    }
+  }
+}
+
+#[cfg(feature = "try_trait")]
+impl<T, E> Residual<T> for State<Infallible, E> {
+  type TryType = State<T, E>;
+}
+
+#[cfg(feature = "try_trait")]
+impl<T, E, F> FromResidual<State<Infallible, E>> for State<T, F>
+where
+  F: From<E>,
+{
+  fn from_residual(residual: State<Infallible, E>) -> Self {
+    match residual {
+      State::Pending => State::Pending,
+      State::Resolve(never) => match never {},
+      State::Resolved => State::Resolved,
+      State::Reject(e) => State::Reject(From::from(e)),
+      State::Rejected => State::Rejected,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[cfg(feature = "serde")]
+  extern crate serde_json;
+
+  use super::*;
+
+  #[test]
+  fn can_map_or_else_resolve() {
+    let state: State<u32, &str> = State::Resolve(2);
+    assert_eq!(state.map_or_else(|e| e.len() as u32, |r| r * 2), 4);
+  }
+
+  #[test]
+  fn can_map_or_else_reject() {
+    let state: State<u32, &str> = State::Reject("oops");
+    assert_eq!(state.map_or_else(|e| e.len() as u32, |r| r * 2), 4);
+  }
+
+  #[test]
+  #[should_panic]
+  fn map_or_else_panics_on_pending() {
+    let state: State<u32, &str> = State::Pending;
+    state.map_or_else(|e| e.len() as u32, |r| r * 2);
+  }
+
+  #[test]
+  fn can_add_resolved_states() {
+    let a: State<u32, ()> = State::Resolve(2);
+    assert_eq!(a + State::Resolve(3), State::Resolve(5));
+  }
+
+  #[test]
+  fn can_sub_and_mul_resolved_states() {
+    let a: State<i32, ()> = State::Resolve(2);
+    let b: State<i32, ()> = State::Resolve(3);
+    assert_eq!(a - b, State::Resolve(-1));
+    assert_eq!(a * b, State::Resolve(6));
+  }
+
+  #[test]
+  fn arithmetic_propagates_reject_before_pending() {
+    let pending: State<u32, &str> = State::Pending;
+    let resolve: State<u32, &str> = State::Resolve(2);
+    let reject_a: State<u32, &str> = State::Reject("a");
+    let reject_b: State<u32, &str> = State::Reject("b");
+    assert_eq!(pending + reject_b, State::Reject("b"));
+    assert_eq!(reject_a + reject_b, State::Reject("a"));
+    assert_eq!(resolve + pending, State::Pending);
+  }
+
+  #[test]
+  fn can_convert_settled_states_to_results() {
+    assert_eq!(State::<u32, u32>::Resolve(1).settled_result(), Ok(Ok(1)));
+    assert_eq!(State::<u32, u32>::Reject(2).settled_result(), Ok(Err(2)));
+  }
+
+  #[test]
+  fn settled_result_gives_back_unsettled_states() {
+    assert_eq!(State::<u32, u32>::Pending.settled_result(), Err(State::Pending));
+    assert_eq!(State::<u32, u32>::Resolved.settled_result(), Err(State::Resolved));
+    assert_eq!(State::<u32, u32>::Rejected.settled_result(), Err(State::Rejected));
+  }
+
+  #[test]
+  fn get_or_insert_keeps_resolved_value() {
+    let mut state: State<u32, ()> = State::Resolve(1);
+    *state.get_or_insert(5) += 1;
+    assert_eq!(state, State::Resolve(2));
+  }
+
+  #[test]
+  fn get_or_insert_resolves_unresolved_state() {
+    let mut state: State<u32, ()> = State::Pending;
+    *state.get_or_insert(5) += 1;
+    assert_eq!(state, State::Resolve(6));
+  }
+
+  #[test]
+  fn xor_returns_the_only_resolved_state() {
+    let resolve: State<u32, &str> = State::Resolve(1);
+    assert_eq!(resolve.xor(State::Pending), State::Resolve(1));
+    assert_eq!(State::Pending.xor(resolve), State::Resolve(1));
+    assert_eq!(State::Reject("a").xor(resolve), State::Resolve(1));
+  }
+
+  #[test]
+  fn xor_of_two_resolved_states_is_pending() {
+    let state: State<u32, &str> = State::Resolve(1);
+    assert_eq!(state.xor(State::Resolve(2)), State::Pending);
+  }
+
+  #[test]
+  fn xor_of_unresolved_states_prefers_first_reject() {
+    let pending: State<u32, &str> = State::Pending;
+    assert_eq!(pending.xor(State::Pending), State::Pending);
+    assert_eq!(pending.xor(State::Reject("b")), State::Reject("b"));
+    let reject: State<u32, &str> = State::Reject("a");
+    assert_eq!(reject.xor(State::Reject("b")), State::Reject("a"));
+  }
+
+  #[test]
+  fn take_result_leaves_terminal_state() {
+    let mut resolve: State<u32, &str> = State::Resolve(1);
+    assert_eq!(resolve.take_result(), Some(Ok(1)));
+    assert_eq!(resolve, State::Resolved);
+    assert_eq!(resolve.take_result(), None);
+
+    let mut reject: State<u32, &str> = State::Reject("oops");
+    assert_eq!(reject.take_result(), Some(Err("oops")));
+    assert_eq!(reject, State::Rejected);
+    assert_eq!(reject.take_result(), None);
+  }
+
+  #[test]
+  fn take_result_leaves_pending_state() {
+    let mut state: State<u32, &str> = State::Pending;
+    assert_eq!(state.take_result(), None);
+    assert_eq!(state, State::Pending);
+  }
+
+  #[test]
+  fn can_get_max_and_min_of_resolved_states() {
+    let a: State<u32, &str> = State::Resolve(3);
+    let b: State<u32, &str> = State::Resolve(7);
+    assert_eq!(a.max(b), State::Resolve(7));
+    assert_eq!(a.min(b), State::Resolve(3));
+  }
+
+  #[test]
+  fn max_and_min_propagate_reject_and_pending() {
+    let resolve: State<u32, &str> = State::Resolve(3);
+    let pending: State<u32, &str> = State::Pending;
+    let reject: State<u32, &str> = State::Reject("oops");
+    assert_eq!(resolve.max(reject), State::Reject("oops"));
+    assert_eq!(pending.min(reject), State::Reject("oops"));
+    assert_eq!(resolve.max(pending), State::Pending);
+    assert_eq!(pending.min(resolve), State::Pending);
+  }
+
+  #[test]
+  fn can_clone_borrowed_states() {
+    let resolve: State<String, String> = State::Resolve(String::from("value"));
+    assert_eq!(resolve.as_ref().cloned(), resolve);
+    let reject: State<String, String> = State::Reject(String::from("error"));
+    assert_eq!(reject.as_ref().cloned(), reject);
+    let pending: State<String, String> = State::Pending;
+    assert_eq!(pending.as_ref().cloned(), pending);
+  }
+
+  #[test]
+  fn can_clone_resolved_value_or_default() {
+    let resolve: State<String, ()> = State::Resolve(String::from("value"));
+    assert_eq!(resolve.as_ref().resolved_cloned_or(String::new()), "value");
+  }
+
+  #[test]
+  fn can_fall_back_to_default_when_not_resolved() {
+    let reject: State<String, ()> = State::Reject(());
+    assert_eq!(reject.as_ref().resolved_cloned_or(String::from("default")), "default");
+    let pending: State<String, ()> = State::Pending;
+    assert_eq!(pending.as_ref().resolved_cloned_or(String::from("default")), "default");
+  }
+
+  #[cfg(feature = "try_trait")]
+  #[test]
+  fn can_use_try_operator_within_task() {
+    use super::super::Task;
+
+    let mut polls = 0;
+    let task: Task<u32, String> = Task::new(move || {
+      polls += 1;
+      let inner: State<u32, &str> = if polls < 3 { State::Pending } else { State::Resolve(1) };
+      let value = inner?;
+      State::Resolve(value + polls)
+    });
+    assert_eq!(task.wait(), Some(Ok(4)));
+  }
+
+  #[cfg(feature = "try_trait")]
+  #[test]
+  fn can_return_early_with_try_operator() {
+    use super::super::Task;
+
+    let task: Task<u32, String> = Task::new(|| {
+      let inner: State<u32, &str> = State::Reject("oops");
+      let value = inner?;
+      State::Resolve(value)
+    });
+    assert_eq!(task.wait(), Some(Err(String::from("oops"))));
+  }
+
+  #[test]
+  fn and_then_peek_borrows_resolved_value() {
+    let state: State<u32, ()> = State::Resolve(2);
+    assert_eq!(state.and_then_peek(|r| State::Resolve(r * 2)), Some(State::Resolve(4)));
+    assert_eq!(state, State::Resolve(2));
+  }
+
+  #[test]
+  fn and_then_peek_skips_pending_state() {
+    let state: State<u32, ()> = State::Pending;
+    assert_eq!(state.and_then_peek(|r| State::Resolve(r * 2)), None);
+    assert_eq!(state, State::Pending);
+  }
+
+  #[test]
+  fn can_count_settled_states() {
+    let states: Vec<State<u32, ()>> = vec![
+      State::Pending,
+      State::Resolve(1),
+      State::Resolved,
+      State::Reject(()),
+      State::Pending,
+      State::Resolve(2),
+    ];
+    assert_eq!(State::count_settled(&states), (2, 3, 1));
+  }
+
+  #[test]
+  fn can_convert_from_option() {
+    assert_eq!(State::from_option(Some(1), "missing"), State::Resolve(1));
+    assert_eq!(State::<u32, _>::from_option(None, "missing"), State::Reject("missing"));
+  }
+
+  #[test]
+  fn can_convert_into_option() {
+    assert_eq!(State::<_, ()>::Resolve(1).into_option(), Some(1));
+    assert_eq!(State::<u32, ()>::Resolved.into_option(), None);
+    assert_eq!(State::<u32, _>::Reject(()).into_option(), None);
+    assert_eq!(State::<u32, ()>::Pending.into_option(), None);
+  }
+
+  #[test]
+  fn can_fold_resolved_states() {
+    let states: Vec<State<u32, &str>> = (1..5).map(State::Resolve).collect();
+    assert_eq!(State::try_fold(states, 0, |a, r| a + r), State::Resolve(10));
+  }
+
+  #[test]
+  fn try_fold_short_circuits_on_reject() {
+    let states = vec![
+      State::Resolve(1),
+      State::Pending,
+      State::Reject("oops"),
+      State::Reject("later"),
+    ];
+    assert_eq!(State::try_fold(states, 0, |a, r| a + r), State::Reject("oops"));
+    let states: Vec<State<u32, &str>> = vec![State::Resolve(1), State::Pending];
+    assert_eq!(State::try_fold(states, 0, |a, r| a + r), State::Pending);
+  }
+
+  #[test]
+  fn can_display_states() {
+    assert_eq!(State::<u32, &str>::Pending.to_string(), "Pending");
+    assert_eq!(State::<u32, &str>::Resolve(42).to_string(), "Resolve(42)");
+    assert_eq!(State::<u32, &str>::Resolved.to_string(), "Resolved");
+    assert_eq!(State::<u32, &str>::Reject("oops").to_string(), "Reject(oops)");
+    assert_eq!(State::<u32, &str>::Rejected.to_string(), "Rejected");
+  }
+
+  #[test]
+  fn can_describe_resolve_state() {
+    let state: State<u32, &str> = State::Resolve(42);
+    assert_eq!(state.describe(), "resolved with 42");
+    assert_eq!(State::<u32, &str>::Resolved.describe(), "resolved, value already taken");
+  }
+
+  #[test]
+  fn can_describe_reject_state() {
+    let state: State<u32, &str> = State::Reject("connection refused");
+    assert_eq!(state.describe(), "rejected with connection refused");
+    assert_eq!(State::<u32, &str>::Rejected.describe(), "rejected, error already taken");
+  }
+
+  #[test]
+  fn can_transpose_resolve_into_reject() {
+    assert_eq!(State::<u32, &str>::Resolve(1).transpose_channels(), State::Reject(1));
+    assert_eq!(State::<u32, &str>::Resolved.transpose_channels(), State::Rejected);
+    assert_eq!(State::<u32, &str>::Pending.transpose_channels(), State::Pending);
+  }
+
+  #[test]
+  fn can_transpose_reject_into_resolve() {
+    assert_eq!(State::<u32, &str>::Reject("found").transpose_channels(), State::Resolve("found"));
+    assert_eq!(State::<u32, &str>::Rejected.transpose_channels(), State::Resolved);
+  }
+
+  #[test]
+  fn is_ok_and_is_err_match_is_resolve_and_is_reject() {
+    let states: [State<u32, u32>; 5] = [
+      State::Pending,
+      State::Resolve(1),
+      State::Resolved,
+      State::Reject(2),
+      State::Rejected,
+    ];
+    for state in &states {
+      assert_eq!(state.is_ok(), state.is_resolve());
+      assert_eq!(state.is_err(), state.is_reject());
+    }
+  }
+
+  #[test]
+  fn can_try_map_resolved_value() {
+    let parse = |s: &str| s.parse::<u32>().map_err(|_| "not a number");
+    assert_eq!(State::Resolve("42").try_map(parse), State::Resolve(42));
+    assert_eq!(State::Resolve("x").try_map(parse), State::Reject("not a number"));
+  }
+
+  #[test]
+  fn can_ensure_resolved_value() {
+    let is_small = |v: &u32| if *v < 10 { Ok(()) } else { Err("too large") };
+    assert_eq!(State::Resolve(4).ensure(is_small), State::Resolve(4));
+    assert_eq!(State::Resolve(30).ensure(is_small), State::Reject("too large"));
+    assert_eq!(State::Pending.ensure(is_small), State::Pending);
+    assert_eq!(State::Reject("failed").ensure(is_small), State::Reject("failed"));
+  }
+
+  #[test]
+  fn try_map_passes_through_other_states() {
+    let double = |v: u32| -> Result<u32, &str> { Ok(v * 2) };
+    assert_eq!(State::Pending.try_map(double), State::Pending);
+    assert_eq!(State::Resolved.try_map(double), State::Resolved);
+    assert_eq!(State::Reject("failed").try_map(double), State::Reject("failed"));
+    assert_eq!(State::Rejected.try_map(double), State::Rejected);
+  }
+
+  #[test]
+  fn and_then_result_resolves_ok_values() {
+    let half = |v: u32| if v < 8 { Err("too small") } else { Ok(v / 2) };
+    assert_eq!(State::Resolve(8).and_then_result(half), State::Resolve(4));
+  }
+
+  #[test]
+  fn and_then_result_rejects_err_values() {
+    let half = |v: u32| if v < 8 { Err("too small") } else { Ok(v / 2) };
+    assert_eq!(State::Resolve(7).and_then_result(half), State::Reject("too small"));
+  }
+
+  #[test]
+  fn and_then_result_passes_through_other_states() {
+    let half = |v: u32| -> Result<u32, &str> { Ok(v / 2) };
+    assert_eq!(State::Pending.and_then_result(half), State::Pending);
+    assert_eq!(State::Resolved.and_then_result(half), State::Resolved);
+    assert_eq!(State::Reject("failed").and_then_result(half), State::Reject("failed"));
+    assert_eq!(State::Rejected.and_then_result(half), State::Rejected);
+  }
+
+  #[test]
+  fn can_convert_ok_result_into_state() {
+    let state: State<u32, &str> = Ok(42).into();
+    assert_eq!(state, State::Resolve(42));
+    assert_eq!(State::from_result(Ok::<u32, &str>(42)), State::Resolve(42));
+  }
+
+  #[test]
+  fn can_convert_err_result_into_state() {
+    let state: State<u32, &str> = Err("failed").into();
+    assert_eq!(state, State::Reject("failed"));
+    assert_eq!(State::from_result(Err::<u32, &str>("failed")), State::Reject("failed"));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn can_round_trip_through_serde() {
+    let states: Vec<State<u32, String>> = vec![
+      State::Pending,
+      State::Resolve(42),
+      State::Resolved,
+      State::Reject("failed".to_string()),
+      State::Rejected,
+    ];
+    for state in states {
+      let json = self::serde_json::to_string(&state).unwrap();
+      assert_eq!(self::serde_json::from_str::<State<u32, String>>(&json).unwrap(), state);
+    }
+    assert_eq!(
+      self::serde_json::to_string(&State::<u32, String>::Resolve(42)).unwrap(),
+      r#"{"state":"Resolve","value":42}"#
+    );
+    assert_eq!(
+      self::serde_json::to_string(&State::<u32, String>::Resolved).unwrap(),
+      r#"{"state":"Resolved"}"#
+    );
+  }
+}
@@ -0,0 +1,51 @@
+use std::sync::{Arc, RwLock};
+use super::State;
+
+/// The kind of a task's state, without its value or error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StateKind {
+  Pending,
+  Resolved,
+  Rejected,
+}
+
+impl StateKind {
+  pub fn of<T, E>(state: &State<T, E>) -> Self {
+    match *state {
+      State::Pending => StateKind::Pending,
+      State::Resolve(_) | State::Resolved => StateKind::Resolved,
+      State::Reject(_) | State::Rejected => StateKind::Rejected,
+    }
+  }
+}
+
+/// A readable snapshot of the state of a running task.
+///
+/// Watches are created with `Task::monitored`, and are updated each time the
+/// task is executed. They can be cloned and read from any thread without
+/// driving the task.
+#[derive(Debug, Clone)]
+pub struct StateWatch(Arc<RwLock<StateKind>>);
+
+impl StateWatch {
+  #[doc(hidden)]
+  pub fn new() -> Self {
+    StateWatch(Arc::new(RwLock::new(StateKind::Pending)))
+  }
+
+  /// Get the kind of state the task was left in by its latest execution
+  pub fn current(&self) -> StateKind {
+    *self.0.read().unwrap()
+  }
+
+  #[doc(hidden)]
+  pub fn update<T, E>(&self, state: &State<T, E>) {
+    *self.0.write().unwrap() = StateKind::of(state);
+  }
+}
+
+impl Default for StateWatch {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -1,10 +1,25 @@
+use std::env;
+use std::error::Error;
 use std::fmt::{self, Debug};
-use std::ops::FnMut;
-use super::State;
-use super::super::runner::Executable;
+use std::mem;
+use std::ops::{BitAnd, BitOr, FnMut};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+use rand::{thread_rng, Rng};
+use super::{RateLimiter, Semaphore, State, StateWatch, TimeoutError};
+use super::super::runner::{Executable, Runner};
 
 #[cfg(feature = "futures_support")]
 use futures::{Async, Future, Poll};
+#[cfg(feature = "futures_support")]
+use super::BufferedStream;
+
+#[cfg(feature = "std_future")]
+use std::future::Future as StdFuture;
+#[cfg(feature = "std_future")]
+use std::pin::Pin;
+#[cfg(feature = "std_future")]
+use std::task::{Context, Poll as StdPoll, Waker};
 
 /// Tasks can be used to execute code in Task Kit's runner thread pool.
 /// This is the key primive of this crate. It can be used to build and
@@ -47,15 +62,42 @@ use futures::{Async, Future, Poll};
 /// # runner.run(task);
 /// # runner.finish();
 /// ```
+///
+/// Tasks are executed upon the runner's worker threads, so their closures,
+/// values, and errors must be `Send`. A task capturing an `Rc` is rejected:
+///
+/// ```compile_fail
+/// # use task_kit::prelude::*;
+/// # use std::rc::Rc;
+/// # let mut runner = Runner::new();
+/// let shared = Rc::new(1);
+/// let task: Task<u32, ()> = Task::new(move || Resolve(*shared));
+/// # runner.run(task);
+/// # runner.finish();
+/// ```
+///
+/// While the same task capturing an `Arc` may be given to the runner:
+///
+/// ```
+/// # use task_kit::prelude::*;
+/// # use std::sync::Arc;
+/// # let mut runner = Runner::new();
+/// let shared = Arc::new(1);
+/// let task: Task<u32, ()> = Task::new(move || Resolve(*shared));
+/// # runner.run(task);
+/// # runner.finish();
+/// ```
 pub struct Task<'a, T = (), E = ()> {
-  task: Box<FnMut() -> State<T, E> + 'a>,
+  task: Box<dyn FnMut() -> State<T, E> + Send + 'a>,
   state: State<T, E>,
+  name: Option<String>,
+  label: Option<&'static str>,
 }
 
 impl<'a, T, E> Task<'a, T, E>
 where
-  T: 'a,
-  E: 'a,
+  T: Send + 'a,
+  E: Send + 'a,
 {
   /// Create a new task from a closure returning a `State`
   ///
@@ -82,11 +124,30 @@ where
   /// ```
   pub fn new<F>(task: F) -> Self
   where
-    F: FnMut() -> State<T, E> + 'a,
+    F: FnMut() -> State<T, E> + Send + 'a,
   {
     Self {
       task: Box::new(task),
       state: State::Pending,
+      name: None,
+      label: None,
+    }
+  }
+
+  /// Create a new task from a boxed closure returning a `State`
+  ///
+  /// Like `new`, but takes a closure that has already been boxed, avoiding
+  /// boxing it a second time.
+  ///
+  /// # Arguments
+  ///
+  /// * `task` - A boxed closure containing code to be executed asyncronously by the runner.
+  pub fn from_boxed(task: Box<dyn FnMut() -> State<T, E> + Send + 'a>) -> Self {
+    Self {
+      task,
+      state: State::Pending,
+      name: None,
+      label: None,
     }
   }
 
@@ -110,17 +171,52 @@ where
   /// # runner.finish();
   /// ```
   pub fn from(val: T) -> Self {
-    let mut val = Some(val);
-    Self::new(move || match val.take() {
-      Some(v) => State::Resolve(v),
-      None => unreachable!(),
+    Self::from_fn_once(move || State::Resolve(val))
+  }
+
+  /// Create a new task that settles with a result
+  ///
+  /// The task resolves with the value of an `Ok`, or rejects with the error
+  /// of an `Err`, the first time it is executed.
+  pub fn from_result(res: Result<T, E>) -> Self {
+    Self::from_fn_once(move || State::from_result(res))
+  }
+
+  /// Create a new task from a closure that will only be called once
+  ///
+  /// Unlike `new`, the closure may consume values it has captured. The
+  /// closure is expected to return a resolved or rejected state; if it
+  /// returns `State::Pending` the task will never settle.
+  ///
+  /// # Arguments
+  ///
+  /// * `task` - A closure returning the state the task should settle with.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use task_kit::prelude::*;
+  /// # let mut runner = Runner::new();
+  /// let name = String::from("task");
+  /// let task: Task<String, ()> = Task::from_fn_once(move || Resolve(name));
+  /// # runner.run(task);
+  /// # runner.finish();
+  /// ```
+  pub fn from_fn_once<F>(task: F) -> Self
+  where
+    F: FnOnce() -> State<T, E> + Send + 'a,
+  {
+    let mut task = Some(task);
+    Self::new(move || match task.take() {
+      Some(task) => task(),
+      None => State::Pending,
     })
   }
 
   #[cfg(feature = "futures_support")]
   pub fn from_future<F>(mut future: F) -> Self
   where
-    F: Future<Item = T, Error = E> + 'a,
+    F: Future<Item = T, Error = E> + Send + 'a,
   {
     Self::new(move || loop {
       match future.poll() {
@@ -133,6 +229,39 @@ where
     })
   }
 
+  /// Create a stream of the results of a collection of tasks
+  ///
+  /// No more than `buffer` of the tasks are executed at once, and results
+  /// are yielded as the tasks settle. See `BufferedStream` for more.
+  #[cfg(feature = "futures_support")]
+  pub fn into_buffered_stream(
+    tasks: Vec<Task<'a, T, E>>,
+    buffer: usize,
+  ) -> BufferedStream<'a, T, E> {
+    BufferedStream::new(tasks, buffer)
+  }
+
+  /// Create a new task from a standard library future
+  ///
+  /// The future is polled once each time the task is executed. As the
+  /// runner executes pending tasks repeatedly, the future is polled with a
+  /// waker that does nothing.
+  #[cfg(feature = "std_future")]
+  pub fn from_std_future<F>(future: F) -> Self
+  where
+    F: StdFuture<Output = Result<T, E>> + Send + 'a,
+  {
+    let mut future = Box::pin(future);
+    Self::new(move || {
+      let mut context = Context::from_waker(Waker::noop());
+      match future.as_mut().poll(&mut context) {
+        StdPoll::Ready(Ok(v)) => State::Resolve(v),
+        StdPoll::Ready(Err(e)) => State::Reject(e),
+        StdPoll::Pending => State::Pending,
+      }
+    })
+  }
+
   /// Create a new task from a closure returning a value.
   ///
   /// The closure will only be executed once by the runner, and is expected to
@@ -155,14 +284,81 @@ where
   /// ```
   pub fn with<F>(mut with: F) -> Self
   where
-    F: FnMut() -> T + 'a,
+    F: FnMut() -> T + Send + 'a,
   {
     Self {
       task: Box::new(move || State::Resolve(with())),
       state: State::Pending,
+      name: None,
+      label: None,
     }
   }
 
+  /// Create a new labeled task from a closure returning a `State`
+  ///
+  /// Labels group tasks for the per label counts reported by
+  /// `Runner::label_stats`, for example by tenant. Like names, labels are not
+  /// carried over by combinators.
+  ///
+  /// # Arguments
+  ///
+  /// * `label` - The label to count the task under.
+  /// * `task` - A closure containing code to be executed asyncronously by the runner.
+  pub fn labeled<F>(label: &'static str, task: F) -> Self
+  where
+    F: FnMut() -> State<T, E> + Send + 'a,
+  {
+    let mut task = Self::new(task);
+    task.label = Some(label);
+    task
+  }
+
+  /// Create a new task from a closure that may only run while holding a
+  /// permit from the given semaphore
+  ///
+  /// The task will acquire a permit before its closure is executed for the
+  /// first time. Until a permit is available the task will remain pending,
+  /// yielding the worker to other tasks. The permit is released once the task
+  /// resolves or rejects, or if the task is dropped.
+  ///
+  /// # Arguments
+  ///
+  /// * `semaphore` - The semaphore to acquire a permit from.
+  /// * `task` - A closure to execute once a permit has been acquired.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use task_kit::prelude::*;
+  /// # use task_kit::task::Semaphore;
+  /// # fn query_database() -> String { String::new() }
+  /// # let mut runner = Runner::new();
+  /// let semaphore = Semaphore::new(4);
+  /// let task: Task<String, ()> = Task::with_permit(&semaphore, || Resolve(query_database()));
+  /// # runner.run(task);
+  /// # runner.finish();
+  /// ```
+  pub fn with_permit<F>(semaphore: &Semaphore, mut task: F) -> Self
+  where
+    F: FnMut() -> State<T, E> + Send + 'a,
+  {
+    let semaphore = semaphore.clone();
+    let mut permit = None;
+    Self::new(move || {
+      if permit.is_none() {
+        permit = semaphore.try_acquire();
+        if permit.is_none() {
+          return State::Pending;
+        }
+      }
+      let state = task();
+      if !state.is_pending() {
+        permit.take();
+      }
+      state
+    })
+  }
+
   /// Create a new merged task from the current task instance and a second task
   ///
   /// Join will return a new task that will resolve a tuple containing the
@@ -188,7 +384,7 @@ where
   /// ```
   pub fn join<U>(mut self, mut task: Task<'a, U, E>) -> Task<'a, (T, U), E>
   where
-    U: 'a,
+    U: Send + 'a,
   {
     Task::new(move || {
       if self.state.is_pending() {
@@ -214,6 +410,214 @@ where
     })
   }
 
+  /// Create a task that runs two tasks one after the other
+  ///
+  /// Unlike `join`, the second task is not executed until the first has
+  /// resolved. The values of both tasks are resolved together. If either
+  /// task rejects, the returned task rejects with its error.
+  pub fn sequence2<U>(mut a: Task<'a, T, E>, mut b: Task<'a, U, E>) -> Task<'a, (T, U), E>
+  where
+    U: Send + 'a,
+  {
+    let mut a_val = None;
+    Task::new(move || {
+      if a_val.is_none() {
+        if !a.exec() {
+          return State::Pending;
+        }
+        match a.state.take() {
+          State::Resolve(v) => a_val = Some(v),
+          State::Reject(e) => return State::Reject(e),
+          _ => unreachable!(),
+        }
+      }
+      if !b.exec() {
+        return State::Pending;
+      }
+      match b.state.take() {
+        State::Resolve(v) => State::Resolve((a_val.take().unwrap(), v)),
+        State::Reject(e) => State::Reject(e),
+        _ => unreachable!(),
+      }
+    })
+  }
+
+  /// Create a task that runs three tasks one after the other
+  ///
+  /// Each task is not executed until the one before it has resolved. See
+  /// `sequence2` for details.
+  pub fn sequence3<U, V>(
+    a: Task<'a, T, E>,
+    b: Task<'a, U, E>,
+    c: Task<'a, V, E>,
+  ) -> Task<'a, (T, U, V), E>
+  where
+    U: Send + 'a,
+    V: Send + 'a,
+  {
+    Task::sequence2(Task::sequence2(a, b), c).map(|((a, b), c)| (a, b, c))
+  }
+
+  /// Create a task that resolves with the values of a collection of tasks
+  ///
+  /// Every task is executed until all have resolved, after which the values
+  /// are resolved in the order the tasks were given. If any of the tasks
+  /// reject, the returned task rejects with the first error found.
+  pub fn all<I>(tasks: I) -> Task<'a, Vec<T>, E>
+  where
+    I: IntoIterator<Item = Task<'a, T, E>>,
+  {
+    let mut tasks: Vec<_> = tasks.into_iter().collect();
+    Task::new(move || {
+      let mut all_resolved = true;
+      for task in tasks.iter_mut() {
+        task.exec();
+        if task.state.is_reject() {
+          return State::Reject(task.state.take().reject().unwrap());
+        }
+        all_resolved = all_resolved && task.state.is_resolve();
+      }
+
+      if all_resolved {
+        State::Resolve(tasks.iter_mut().map(|t| t.state.take().resolve().unwrap()).collect())
+      } else {
+        State::Pending
+      }
+    })
+  }
+
+  /// Create a task that settles with the first of a collection of tasks to
+  /// settle
+  ///
+  /// Every task is executed in the order given until one of them resolves
+  /// or rejects. If several settle within the same execution, the first in
+  /// the order given wins. The remaining tasks are then dropped. Given no
+  /// tasks, the returned task is pending forever.
+  pub fn race<I>(tasks: I) -> Task<'a, T, E>
+  where
+    I: IntoIterator<Item = Task<'a, T, E>>,
+  {
+    let mut tasks: Vec<_> = tasks.into_iter().collect();
+    Task::new(move || {
+      let settled = tasks.iter_mut().position(|task| task.exec());
+      match settled {
+        Some(index) => {
+          let state = tasks[index].state.take();
+          tasks.clear();
+          state
+        }
+        None => State::Pending,
+      }
+    })
+  }
+
+  /// Create a task that resolves with the first of two tasks to resolve
+  ///
+  /// Both tasks are executed until one of them resolves. A rejection is only
+  /// passed on once both tasks have rejected, in which case the error of the
+  /// given task is used.
+  pub fn race_ok(mut self, mut task: Task<'a, T, E>) -> Task<'a, T, E> {
+    Task::new(move || {
+      self.exec();
+      task.exec();
+
+      if self.state.is_resolve() {
+        return State::Resolve(self.state.take().resolve().unwrap());
+      }
+      if task.state.is_resolve() {
+        return State::Resolve(task.state.take().resolve().unwrap());
+      }
+      if self.state.is_reject() && task.state.is_reject() {
+        return State::Reject(task.state.take().reject().unwrap());
+      }
+      State::Pending
+    })
+  }
+
+  /// Create a task that retries a task until it resolves
+  ///
+  /// A fresh task is built with `factory` for each attempt, and a new attempt
+  /// is made each time one rejects. The task resolves with the first attempt
+  /// to resolve, or rejects with the error of the last attempt once
+  /// `attempts` attempts have rejected. At least one attempt is always made.
+  ///
+  /// # Arguments
+  ///
+  /// * `factory` - A closure building the task for each attempt.
+  /// * `attempts` - The maximum number of attempts to make.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use task_kit::prelude::*;
+  /// # fn fetch_page() -> Task<'static, String, ()> { Task::from(String::new()) }
+  /// # let mut runner = Runner::new();
+  /// let task: Task<String, ()> = Task::retry_with(fetch_page, 3);
+  /// # runner.run(task);
+  /// # runner.finish();
+  /// ```
+  pub fn retry_with<F>(mut factory: F, attempts: usize) -> Self
+  where
+    F: FnMut() -> Task<'a, T, E> + Send + 'a,
+  {
+    let mut attempt = 1;
+    let mut task = factory();
+    Self::new(move || {
+      task.exec();
+      match task.state.take() {
+        State::Reject(_) if attempt < attempts => {
+          attempt += 1;
+          task = factory();
+          State::Pending
+        }
+        state => state,
+      }
+    })
+  }
+
+  /// Create a task that runs tasks made by a factory a number of times in
+  /// sequence
+  ///
+  /// The factory is given the index of each run, and the next task is only
+  /// made once the one before it resolves. The values are resolved in the
+  /// order they were produced. If any of the tasks reject, the returned task
+  /// rejects with that error and no further tasks are made.
+  pub fn repeat<F>(mut factory: F, times: usize) -> Task<'a, Vec<T>, E>
+  where
+    F: FnMut(usize) -> Task<'a, T, E> + Send + 'a,
+  {
+    let mut values = Vec::with_capacity(times);
+    let mut task = None;
+    Task::new(move || {
+      while values.len() < times {
+        let current = task.get_or_insert_with(|| factory(values.len()));
+        current.exec();
+        match current.state.take() {
+          State::Resolve(v) => {
+            values.push(v);
+            task = None;
+          }
+          State::Reject(e) => return State::Reject(e),
+          _ => return State::Pending,
+        }
+      }
+      State::Resolve(mem::take(&mut values))
+    })
+  }
+
+  /// Name the task
+  ///
+  /// The name is passed to the lifecycle callbacks registered on the runner.
+  /// Note that combinators return a new, unnamed task, so a name should be
+  /// given to the task that is passed to the runner.
+  pub fn named<S>(mut self, name: S) -> Self
+  where
+    S: Into<String>,
+  {
+    self.name = Some(name.into());
+    self
+  }
+
   /// Get the task state
   ///
   /// Returns a reference to the internal state of the task
@@ -227,11 +631,7 @@ where
   /// a result object.
   pub fn poll(&mut self) -> Option<Result<T, E>> {
     self.exec();
-    if self.state.is_pending() {
-      None
-    } else {
-      self.state.take().into_result()
-    }
+    self.state.take_result()
   }
 
   /// Executes the closure within the task blocking until the task completes
@@ -244,74 +644,479 @@ where
     }
   }
 
-  pub fn map<F, U>(self, mut map: F) -> Task<'a, U, E>
+  /// Spawns the task upon a runner, blocking until the task completes
+  ///
+  /// Unlike `wait`, the task is executed by the runner's workers, allowing
+  /// it to depend upon other work within the pool.
+  pub fn drive_on(self, runner: &mut Runner) -> Option<Result<T, E>>
   where
-    F: FnMut(T) -> U + 'a,
-    U: 'a,
+    Self: 'static,
   {
-    self.then(move |v| State::Resolve(map(v)))
+    runner.spawn(self).wait()
   }
 
-  pub fn then<F, U>(mut self, mut task: F) -> Task<'a, U, E>
+  /// Warns upon stderr when a single execution of the task exceeds a
+  /// duration
+  ///
+  /// Tasks are expected to yield quickly, so a closure that blocks stalls the
+  /// worker executing it. This helps to find such closures. The warning
+  /// includes the name and label of the task, if it has them. Executions are
+  /// only timed in debug builds; in release builds the task is returned
+  /// unchanged.
+  pub fn time_limited_poll(self, max: Duration) -> Task<'a, T, E> {
+    self.time_limited_poll_with(max, move |elapsed, name, label| {
+      eprintln!(
+        "task_kit: task {} (label {}) execution took {:?}, exceeding the limit of {:?}",
+        name.unwrap_or("<unnamed>"),
+        label.unwrap_or("<none>"),
+        elapsed,
+        max
+      )
+    })
+  }
+
+  /// Calls a closure when a single execution of the task exceeds a duration
+  ///
+  /// The closure is given how long the execution took, along with the name
+  /// and label of the task so that the offending task can be identified.
+  /// The returned task keeps the name and label. As with
+  /// `time_limited_poll`, executions are only timed in debug builds.
+  pub fn time_limited_poll_with<F>(mut self, max: Duration, mut on_exceeded: F) -> Task<'a, T, E>
   where
-    F: FnMut(T) -> State<U, E> + 'a,
-    U: 'a,
+    F: FnMut(Duration, Option<&str>, Option<&'static str>) + Send + 'a,
   {
+    if !cfg!(debug_assertions) {
+      return self;
+    }
+    let name = self.name.clone();
+    let label = self.label;
+    let mut task = Task::new(move || {
+      let start = Instant::now();
+      self.exec();
+      let elapsed = start.elapsed();
+      if elapsed > max {
+        on_exceeded(elapsed, self.name.as_deref(), self.label);
+      }
+      self.state.take()
+    });
+    task.name = name;
+    task.label = label;
+    task
+  }
+
+  /// Logs each execution of the task to stderr when the `TASK_KIT_TRACE`
+  /// environment variable is set
+  ///
+  /// Each line includes the label given, the number of times the task has
+  /// been executed, and the resulting state. The environment variable is
+  /// checked when this method is called; if it is not set the task is
+  /// returned unchanged.
+  pub fn debug_trace(mut self, label: &'static str) -> Task<'a, T, E> {
+    if env::var_os("TASK_KIT_TRACE").is_none() {
+      return self;
+    }
+    let mut polls = 0;
     Task::new(move || {
       self.exec();
+      polls += 1;
+      eprintln!("task_kit: [{}] poll {}: {:?}", label, polls, self.state);
+      self.state.take()
+    })
+  }
 
-      if !self.state.is_pending() {
-        match self.state.take().into_result().unwrap() {
-          Ok(r) => task(r),
-          Err(e) => State::Reject(e),
+  /// Sends intermediate values produced while the task is pending to a
+  /// channel
+  ///
+  /// After each execution that leaves the task pending, `f` is called and any
+  /// value it returns is sent to the returned receiver. This is only useful
+  /// for tasks that step towards their result over many executions, where
+  /// `f` can observe their progress. The result of the task is unaffected.
+  pub fn stream_pending<U, F>(mut self, mut f: F) -> (Task<'a, T, E>, Receiver<U>)
+  where
+    F: FnMut() -> Option<U> + Send + 'a,
+    U: Send + 'a,
+  {
+    let (sender, receiver) = channel();
+    let task = Task::new(move || {
+      self.exec();
+      if self.state.is_pending() {
+        if let Some(value) = f() {
+          let _ = sender.send(value);
         }
-      } else {
-        State::Pending
       }
+      self.state.take()
+    });
+    (task, receiver)
+  }
+
+  /// Create a task that only executes the current task when a token is
+  /// available from the given rate limiter
+  ///
+  /// Each execution of the current task takes a token. Until one is
+  /// available the task remains pending, yielding the worker to other tasks.
+  /// Tasks sharing clones of the same limiter respect one combined rate.
+  pub fn rate_limited(mut self, limiter: &RateLimiter) -> Task<'a, T, E> {
+    let limiter = limiter.clone();
+    Task::new(move || {
+      if !limiter.try_acquire() {
+        return State::Pending;
+      }
+      self.exec();
+      self.state.take()
     })
   }
 
-  pub fn done<F>(self, mut done: F) -> Task<'a, (), E>
+  /// Create a task that waits for a duration before executing the current
+  /// task
+  ///
+  /// The returned task is pending until `dur` has elapsed from its first
+  /// execution, after which the current task is executed as normal. The
+  /// returned task keeps the name and label of the current task.
+  pub fn after(mut self, dur: Duration) -> Task<'a, T, E> {
+    let name = self.name.clone();
+    let label = self.label;
+    let mut started_at = None;
+    let mut task = Task::new(move || {
+      let started_at = started_at.get_or_insert_with(Instant::now);
+      if started_at.elapsed() < dur {
+        return State::Pending;
+      }
+      self.exec();
+      self.state.take()
+    });
+    task.name = name;
+    task.label = label;
+    task
+  }
+
+  /// Create a task that waits for a duration before executing the current
+  /// task. This is the same as `after`.
+  pub fn delay(self, dur: Duration) -> Task<'a, T, E> {
+    self.after(dur)
+  }
+
+  /// Create a task that rejects if the current task does not settle in time
+  ///
+  /// The deadline starts when the task is first executed rather than when it
+  /// is created. The current task is raced against a delayed rejection, so
+  /// once the deadline has passed the task rejects with a `TimeoutError` and
+  /// the current task is dropped without being executed again.
+  pub fn timeout(self, dur: Duration) -> Task<'a, T, E>
   where
-    F: FnMut(T) + 'a,
+    E: From<TimeoutError>,
   {
-    self.then(move |r| State::Resolve(done(r)))
+    let deadline = Task::from_fn_once(|| State::Reject(TimeoutError.into())).after(dur);
+    Task::race(vec![self, deadline])
   }
 
-  pub fn recover<F, O>(mut self, mut recover: F) -> Task<'a, T, O>
+  /// Create a task that rejects if the current task stops making progress
+  ///
+  /// After each execution that leaves the task pending, `is_progress` is
+  /// called to check if the task has made progress. If it has not made
+  /// progress for `dur`, the task rejects with a `TimeoutError`.
+  pub fn idle_timeout<F>(mut self, dur: Duration, mut is_progress: F) -> Task<'a, T, E>
   where
-    F: FnMut(E) -> State<T, O> + 'a,
-    O: 'a,
+    F: FnMut() -> bool + Send + 'a,
+    E: From<TimeoutError>,
   {
+    let mut last_progress_at = None;
     Task::new(move || {
-      self.exec();
+      let last_progress = last_progress_at.get_or_insert_with(Instant::now);
+      if self.exec() {
+        return self.state.take();
+      }
+      if is_progress() {
+        *last_progress = Instant::now();
+      } else if last_progress.elapsed() >= dur {
+        return State::Reject(TimeoutError.into());
+      }
+      State::Pending
+    })
+  }
 
-      if !self.state.is_pending() {
-        match self.state.take().into_result().unwrap() {
-          Ok(r) => State::Resolve(r),
-          Err(e) => recover(e),
-        }
-      } else {
-        State::Pending
+  /// Create a task that resolves with the value of the current task along
+  /// with the time it took to resolve
+  ///
+  /// The time is measured from the first execution of the task. Rejections
+  /// are passed through without a duration.
+  pub fn timed(mut self) -> Task<'a, (T, Duration), E> {
+    let mut started_at = None;
+    Task::new(move || {
+      let started_at = started_at.get_or_insert_with(Instant::now);
+      self.exec();
+      match self.state.take_result() {
+        Some(Ok(r)) => State::Resolve((r, started_at.elapsed())),
+        Some(Err(e)) => State::Reject(e),
+        None => State::Pending,
       }
     })
   }
 
-  pub fn catch<F>(self, mut catch: F) -> Task<'a, T, ()>
+  /// Create a task along with a watch for observing its state
+  ///
+  /// The watch is updated each time the task is executed, and can be read
+  /// from another thread, for example to report the progress of tasks given
+  /// to the runner.
+  pub fn monitored(mut self) -> (Task<'a, T, E>, StateWatch) {
+    let watch = StateWatch::new();
+    let task_watch = watch.clone();
+    let task = Task::new(move || {
+      self.exec();
+      task_watch.update(&self.state);
+      self.state.take()
+    });
+    (task, watch)
+  }
+
+  /// Create a task that calls a cleanup closure if it is dropped before it
+  /// settles
+  ///
+  /// This is useful for releasing resources held by tasks that may be
+  /// abandoned, such as the losers of `Task::race`. Once the task resolves
+  /// or rejects, the cleanup closure is discarded without being called, so
+  /// it never runs alongside a closure given to `finally`.
+  pub fn on_drop<F>(mut self, f: F) -> Task<'a, T, E>
   where
-    F: FnMut(E) + 'a,
+    F: FnOnce() + Send + 'a,
   {
-    self.recover(move |e| State::Reject(catch(e)))
+    let mut guard = DropGuard(Some(f));
+    Task::new(move || {
+      if self.exec() {
+        guard.0.take();
+      }
+      self.state.take()
+    })
   }
-}
 
-impl<'a, T, E> Debug for Task<'a, T, E> {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "Task {{ state: {:?} }}", self.state)
+  /// Create a task that calls a closure once the current task settles
+  ///
+  /// The closure is given the state the task settled with, whether it
+  /// resolved or rejected, and is called exactly once. The state is then
+  /// passed through unchanged. To clean up after tasks that are dropped
+  /// before they settle, see `on_drop`.
+  pub fn finally<F>(mut self, mut f: F) -> Task<'a, T, E>
+  where
+    F: FnMut(&State<T, E>) + Send + 'a,
+  {
+    Task::new(move || {
+      if self.exec() {
+        f(&self.state);
+      }
+      self.state.take()
+    })
   }
-}
 
-impl<'a, T, E> Executable for Task<'a, T, E> {
+  /// Create a task that calls a closure with the value of the current task
+  /// once it resolves
+  ///
+  /// The value is passed through unchanged, making this useful for logging
+  /// or debugging. The closure is not called if the task rejects.
+  pub fn inspect<F>(mut self, mut f: F) -> Task<'a, T, E>
+  where
+    F: FnMut(&T) + Send + 'a,
+  {
+    Task::new(move || {
+      self.exec();
+      if let State::Resolve(ref r) = self.state {
+        f(r);
+      }
+      self.state.take()
+    })
+  }
+
+  /// Create a task that calls a closure with the error of the current task
+  /// once it rejects
+  ///
+  /// The error is passed through unchanged. The closure is not called if the
+  /// task resolves.
+  pub fn inspect_err<F>(mut self, mut f: F) -> Task<'a, T, E>
+  where
+    F: FnMut(&E) + Send + 'a,
+  {
+    Task::new(move || {
+      self.exec();
+      if let State::Reject(ref e) = self.state {
+        f(e);
+      }
+      self.state.take()
+    })
+  }
+
+  /// Create a task that sends the result of the current task to a channel
+  ///
+  /// The returned task resolves once the result has been sent. If the
+  /// receiver has been dropped the result is discarded.
+  pub fn with_result_sender(mut self, sender: Sender<Result<T, E>>) -> Task<'a, (), ()> {
+    Task::new(move || match self.poll() {
+      Some(result) => {
+        let _ = sender.send(result);
+        State::Resolve(())
+      }
+      None => State::Pending,
+    })
+  }
+
+  pub fn map<F, U>(self, mut map: F) -> Task<'a, U, E>
+  where
+    F: FnMut(T) -> U + Send + 'a,
+    U: Send + 'a,
+  {
+    self.then(move |v| State::Resolve(map(v)))
+  }
+
+  pub fn then<F, U>(mut self, mut task: F) -> Task<'a, U, E>
+  where
+    F: FnMut(T) -> State<U, E> + Send + 'a,
+    U: Send + 'a,
+  {
+    Task::new(move || {
+      self.exec();
+
+      match self.state.take_result() {
+        Some(Ok(r)) => task(r),
+        Some(Err(e)) => State::Reject(e),
+        None => State::Pending,
+      }
+    })
+  }
+
+  /// Create a task that continues with the task returned by a closure
+  ///
+  /// Like `then`, but the closure returns a task rather than a state. Once
+  /// the current task resolves, the returned task is executed until it
+  /// settles. A rejection from either task is passed through.
+  pub fn and_then<F, U>(self, f: F) -> Task<'a, U, E>
+  where
+    F: FnMut(T) -> Task<'a, U, E> + Send + 'a,
+    U: Send + 'a,
+  {
+    self.map(f).flatten()
+  }
+
+  pub fn done<F>(self, mut done: F) -> Task<'a, (), E>
+  where
+    F: FnMut(T) + Send + 'a,
+  {
+    self.then(move |r| State::Resolve(done(r)))
+  }
+
+  pub fn recover<F, O>(mut self, mut recover: F) -> Task<'a, T, O>
+  where
+    F: FnMut(E) -> State<T, O> + Send + 'a,
+    O: Send + 'a,
+  {
+    Task::new(move || {
+      self.exec();
+
+      match self.state.take_result() {
+        Some(Ok(r)) => State::Resolve(r),
+        Some(Err(e)) => recover(e),
+        None => State::Pending,
+      }
+    })
+  }
+
+  /// Create a task that recovers from a rejection by running another task
+  ///
+  /// Like `recover`, but rather than settling immediately, the task built by
+  /// `f` from the error is executed until it settles. This is the canonical
+  /// method for recovering with a task; `or_else` is an alias of it.
+  pub fn recover_with<F, O>(mut self, mut f: F) -> Task<'a, T, O>
+  where
+    F: FnMut(E) -> Task<'a, T, O> + Send + 'a,
+    O: Send + 'a,
+  {
+    let mut recovery: Option<Task<'a, T, O>> = None;
+    Task::new(move || {
+      if recovery.is_none() {
+        self.exec();
+        match self.state.take_result() {
+          Some(Ok(r)) => return State::Resolve(r),
+          Some(Err(e)) => recovery = Some(f(e)),
+          None => return State::Pending,
+        }
+      }
+
+      let recovery = recovery.as_mut().unwrap();
+      recovery.exec();
+      recovery.state.take()
+    })
+  }
+
+  /// Create a task that continues with the task returned by a closure on
+  /// rejection
+  ///
+  /// The counterpart of `and_then` for rejections. This is an alias of
+  /// `recover_with`, which should be preferred.
+  pub fn or_else<F, O>(self, f: F) -> Task<'a, T, O>
+  where
+    F: FnMut(E) -> Task<'a, T, O> + Send + 'a,
+    O: Send + 'a,
+  {
+    self.recover_with(f)
+  }
+
+  pub fn catch<F>(self, mut catch: F) -> Task<'a, T, ()>
+  where
+    F: FnMut(E) + Send + 'a,
+  {
+    self.recover(move |e| State::Reject(catch(e)))
+  }
+
+  /// Create a task that pairs the current task's error with a context value
+  ///
+  /// This is useful for recording which stage of a chain of tasks produced
+  /// an error, such as a stage name or id.
+  pub fn context<C>(self, ctx: C) -> Task<'a, T, (C, E)>
+  where
+    C: Clone + Send + 'a,
+  {
+    self.recover(move |e| State::Reject((ctx.clone(), e)))
+  }
+
+  /// Create a task that rejects with the current task's error boxed
+  ///
+  /// This allows tasks with different error types to be combined with one
+  /// another.
+  pub fn erase_error(self) -> Task<'a, T, Box<dyn Error + Send + Sync>>
+  where
+    E: Error + Send + Sync + 'static,
+  {
+    self.recover(|e| State::Reject(Box::new(e) as Box<dyn Error + Send + Sync>))
+  }
+
+  /// Create a task that resolves the state the current task settles with
+  ///
+  /// A rejection becomes a resolved `State::Reject`, allowing outcomes to be
+  /// passed through code that only handles resolved values. The returned task
+  /// never rejects. Use `dematerialize` to reverse this.
+  pub fn materialize<O>(mut self) -> Task<'a, State<T, E>, O>
+  where
+    O: Send + 'a,
+  {
+    Task::new(move || {
+      self.exec();
+      if self.state.is_pending() {
+        State::Pending
+      } else {
+        State::Resolve(self.state.take())
+      }
+    })
+  }
+
+}
+
+impl<'a, T, E> Debug for Task<'a, T, E> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Task {{ state: {:?} }}", self.state)
+  }
+}
+
+impl<'a, T, E> Executable for Task<'a, T, E>
+where
+  T: Send,
+  E: Send,
+{
   fn exec(&mut self) -> bool {
     if !self.state.is_pending() {
       return true;
@@ -320,12 +1125,170 @@ impl<'a, T, E> Executable for Task<'a, T, E> {
     self.state = (self.task)();
     !self.state.is_pending()
   }
+
+  fn name(&self) -> Option<&str> {
+    self.name.as_deref()
+  }
+
+  fn label(&self) -> Option<&'static str> {
+    self.label
+  }
+}
+
+impl<'a, T, E> Task<'a, State<T, E>, E>
+where
+  T: Send + 'a,
+  E: Send + 'a,
+{
+  /// Create a task that settles with the state resolved by the current task
+  ///
+  /// This reverses `materialize`. A rejection of the current task is passed
+  /// through as is.
+  pub fn dematerialize(mut self) -> Task<'a, T, E> {
+    Task::new(move || {
+      self.exec();
+      match self.state.take_result() {
+        Some(Ok(state)) => state,
+        Some(Err(e)) => State::Reject(e),
+        None => State::Pending,
+      }
+    })
+  }
+}
+
+impl<'a, U, E> Task<'a, Result<U, E>, E>
+where
+  U: Send + 'a,
+  E: Send + 'a,
+{
+  /// Create a task that unwraps the result resolved by the current task
+  ///
+  /// A resolved `Ok` resolves with its value, while a resolved `Err` rejects
+  /// with its error, along with any rejection of the current task.
+  pub fn flatten_result(mut self) -> Task<'a, U, E> {
+    Task::new(move || {
+      self.exec();
+      match self.state.take_result() {
+        Some(Ok(Ok(r))) => State::Resolve(r),
+        Some(Ok(Err(e))) | Some(Err(e)) => State::Reject(e),
+        None => State::Pending,
+      }
+    })
+  }
+}
+
+impl<'a, T, E> Task<'a, Task<'a, T, E>, E>
+where
+  T: Send + 'a,
+  E: Send + 'a,
+{
+  /// Create a task that settles with the task resolved by the current task
+  ///
+  /// The current task is executed until it resolves, after which the task it
+  /// resolved with is executed until it settles. A rejection from either
+  /// task is passed through.
+  pub fn flatten(mut self) -> Task<'a, T, E> {
+    let mut inner = None;
+    Task::new(move || {
+      if inner.is_none() {
+        self.exec();
+        match self.state.take_result() {
+          Some(Ok(task)) => inner = Some(task),
+          Some(Err(e)) => return State::Reject(e),
+          None => return State::Pending,
+        }
+      }
+      let inner = inner.as_mut().unwrap();
+      inner.exec();
+      inner.state.take()
+    })
+  }
+}
+
+impl<'a, E> Task<'a, (), E>
+where
+  E: Send + 'a,
+{
+  /// Create a new task that resolves after a randomized delay
+  ///
+  /// The task resolves once `base` plus a random duration of up to `jitter`
+  /// has elapsed from its first execution. Randomizing delays helps avoid
+  /// many tasks retrying in lockstep against the same resource.
+  ///
+  /// # Arguments
+  ///
+  /// * `base` - The minimum duration to wait.
+  /// * `jitter` - The maximum random duration added to `base`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use task_kit::prelude::*;
+  /// # use std::time::Duration;
+  /// # let mut runner = Runner::new();
+  /// let task: Task<(), ()> =
+  ///   Task::delay_jittered(Duration::from_millis(10), Duration::from_millis(5));
+  /// # runner.run(task);
+  /// # runner.finish();
+  /// ```
+  pub fn delay_jittered(base: Duration, jitter: Duration) -> Self {
+    Self::delay_jittered_with_rng(base, jitter, &mut thread_rng())
+  }
+
+  /// Create a task that resolves after a randomized delay, drawn from a
+  /// given random number generator
+  ///
+  /// This is the same as `delay_jittered`, except that the random duration
+  /// is drawn from `rng` rather than the thread's generator. Passing a
+  /// seeded generator makes the delays reproducible.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # extern crate rand;
+  /// # extern crate task_kit;
+  /// # use task_kit::prelude::*;
+  /// # use std::time::Duration;
+  /// use rand::{SeedableRng, XorShiftRng};
+  ///
+  /// # fn main() {
+  /// # let mut runner = Runner::new();
+  /// let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+  /// let task: Task<(), ()> = Task::delay_jittered_with_rng(
+  ///   Duration::from_millis(10),
+  ///   Duration::from_millis(5),
+  ///   &mut rng,
+  /// );
+  /// # runner.run(task);
+  /// # runner.finish();
+  /// # }
+  /// ```
+  pub fn delay_jittered_with_rng<R>(base: Duration, jitter: Duration, rng: &mut R) -> Self
+  where
+    R: Rng,
+  {
+    let jitter_nanos = jitter.as_nanos() as u64;
+    let delay = if jitter_nanos > 0 {
+      base + Duration::from_nanos(rng.gen_range(0, jitter_nanos + 1))
+    } else {
+      base
+    };
+    let mut started_at = None;
+    Self::new(move || {
+      let started_at = started_at.get_or_insert_with(Instant::now);
+      if started_at.elapsed() >= delay {
+        State::Resolve(())
+      } else {
+        State::Pending
+      }
+    })
+  }
 }
 
 impl<'a, T, E> Task<'a, T, E>
 where
-  T: PartialEq + 'a,
-  E: PartialEq + 'a,
+  T: PartialEq + Send + 'a,
+  E: PartialEq + Send + 'a,
 {
   pub fn eq(self, task: Task<'a, T, E>) -> Task<'a, bool, E> {
     self.join(task).map(|(a, b)| a == b)
@@ -337,8 +1300,8 @@ where
 
 impl<'a, T, E> Task<'a, T, E>
 where
-  T: PartialOrd + 'a,
-  E: PartialOrd + 'a,
+  T: PartialOrd + Send + 'a,
+  E: PartialOrd + Send + 'a,
 {
   pub fn lt(self, task: Task<'a, T, E>) -> Task<'a, bool, E> {
     self.join(task).map(|(a, b)| a < b)
@@ -354,23 +1317,44 @@ where
   }
 }
 
-#[cfg(feature = "futures_support")]
-impl<'a, T, E, F> From<F> for Task<'a, T, E>
+/// Joins two tasks with `&`
+///
+/// `a & b` is the same as `a.join(b)`, resolving with a tuple of both values
+/// or rejecting with the first error.
+impl<'a, T, U, E> BitAnd<Task<'a, U, E>> for Task<'a, T, E>
+where
+  T: Send + 'a,
+  U: Send + 'a,
+  E: Send + 'a,
+{
+  type Output = Task<'a, (T, U), E>;
+
+  fn bitand(self, task: Task<'a, U, E>) -> Self::Output {
+    self.join(task)
+  }
+}
+
+/// Races two tasks with `|`
+///
+/// `a | b` is the same as `a.race_ok(b)`, resolving with the value of the
+/// first task to resolve. It only rejects if both tasks reject.
+impl<'a, T, E> BitOr for Task<'a, T, E>
 where
-  T: 'a,
-  E: 'a,
-  F: Future<Item = T, Error = E> + 'a,
+  T: Send + 'a,
+  E: Send + 'a,
 {
-  fn from(future: F) -> Self {
-    Self::from_future(future)
+  type Output = Task<'a, T, E>;
+
+  fn bitor(self, task: Task<'a, T, E>) -> Self::Output {
+    self.race_ok(task)
   }
 }
 
 #[cfg(feature = "futures_support")]
 impl<'a, T, E> Future for Task<'a, T, E>
 where
-  T: 'a,
-  E: 'a,
+  T: Send + 'a,
+  E: Send + 'a,
 {
   type Item = T;
   type Error = E;
@@ -378,11 +1362,8 @@ where
   fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
     self.exec();
 
-    if let State::Pending = self.state {
-      return Ok(Async::NotReady);
-    }
-
     match self.state.take() {
+      State::Pending => Ok(Async::NotReady),
       State::Resolve(v) => Ok(Async::Ready(v)),
       State::Reject(e) => Err(e),
       State::Resolved => panic!("Task already resolved"),
@@ -391,8 +1372,48 @@ where
   }
 }
 
-unsafe impl<'a, T, E> Send for Task<'a, T, E> {}
-unsafe impl<'a, T, E> Sync for Task<'a, T, E> {}
+#[cfg(feature = "std_future")]
+impl<'a, T, E> StdFuture for Task<'a, T, E>
+where
+  T: Send + 'a,
+  E: Send + 'a,
+{
+  type Output = Result<T, E>;
+
+  // Tasks have no way to signal when they can make progress, so the waker is
+  // woken straight away to have the executor poll the task again.
+  fn poll(self: Pin<&mut Self>, context: &mut Context) -> StdPoll<Self::Output> {
+    let task = self.get_mut();
+    task.exec();
+
+    match task.state.take() {
+      State::Pending => {
+        context.waker().wake_by_ref();
+        StdPoll::Pending
+      }
+      State::Resolve(v) => StdPoll::Ready(Ok(v)),
+      State::Reject(e) => StdPoll::Ready(Err(e)),
+      State::Resolved => panic!("Task already resolved"),
+      State::Rejected => panic!("Task already rejected"),
+    }
+  }
+}
+
+// Pinning a task does not pin its closure or its values, as they are never
+// handed out as pinned references, so a task may be moved freely even when
+// its values are not `Unpin`. This lets `poll` take the task by `get_mut`.
+#[cfg(feature = "std_future")]
+impl<'a, T, E> Unpin for Task<'a, T, E> {}
+
+struct DropGuard<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for DropGuard<F> {
+  fn drop(&mut self) {
+    if let Some(f) = self.0.take() {
+      f();
+    }
+  }
+}
 
 #[cfg(test)]
 mod tests {
@@ -403,7 +1424,7 @@ mod tests {
 
   #[test]
   fn can_create_task() {
-    let _: Task<(), ()> = Task::new(|| State::Pending);
+    let _task: Task<(), ()> = Task::new(|| State::Pending);
   }
 
   #[cfg(feature = "futures_support")]
@@ -413,7 +1434,41 @@ mod tests {
     use std::time::Duration;
 
     let sleep_future = Timer::default().sleep(Duration::new(1, 0));
-    let _: Task<(), TimerError> = Task::from_future(sleep_future);
+    let _task: Task<(), TimerError> = Task::from_future(sleep_future);
+  }
+
+  #[cfg(feature = "std_future")]
+  #[test]
+  fn can_create_task_from_std_future() {
+    use std::future;
+
+    let mut polls = 0;
+    let task: Task<_, ()> = Task::from_std_future(future::poll_fn(move |_| {
+      polls += 1;
+      if polls == 3 {
+        StdPoll::Ready(Ok(polls))
+      } else {
+        StdPoll::Pending
+      }
+    }));
+    assert_eq!(task.wait(), Some(Ok(3)));
+  }
+
+  #[cfg(feature = "std_future")]
+  #[test]
+  fn can_poll_task_as_std_future() {
+    let mut task = resolve_after(3, 42);
+    let mut context = Context::from_waker(Waker::noop());
+    assert_eq!(Pin::new(&mut task).poll(&mut context), StdPoll::Pending);
+    assert_eq!(Pin::new(&mut task).poll(&mut context), StdPoll::Pending);
+    assert_eq!(Pin::new(&mut task).poll(&mut context), StdPoll::Ready(Ok(42)));
+  }
+
+  #[test]
+  fn can_create_task_from_fn_once() {
+    let values = vec![1, 2, 3];
+    let task: Task<_, ()> = Task::from_fn_once(move || State::Resolve(values));
+    assert_eq!(task.wait(), Some(Ok(vec![1, 2, 3])));
   }
 
   #[test]
@@ -458,9 +1513,749 @@ mod tests {
     assert_eq!(task.wait().unwrap().unwrap(), 7);
   }
 
+  #[test]
+  fn can_limit_concurrency_with_permits() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let semaphore = Semaphore::new(2);
+    let active = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+
+    let mut runner = Runner::with_worker_count(4);
+    for _ in 0..10 {
+      let active = active.clone();
+      let peak = peak.clone();
+      let mut polls = 0;
+      runner.run(Task::<(), ()>::with_permit(&semaphore, move || {
+        if polls == 0 {
+          let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+          peak.fetch_max(now_active, Ordering::SeqCst);
+        }
+        polls += 1;
+        if polls < 1000 {
+          return State::Pending;
+        }
+        active.fetch_sub(1, Ordering::SeqCst);
+        State::Resolve(())
+      }));
+    }
+    runner.finish();
+
+    assert!(peak.load(Ordering::SeqCst) <= 2);
+    assert_eq!(semaphore.available(), 2);
+  }
+
+  #[test]
+  fn with_permit_waits_for_a_permit_to_be_released() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let semaphore = Semaphore::new(2);
+    let calls = AtomicUsize::new(0);
+    let mut holders: Vec<Task<(), ()>> = (0..2)
+      .map(|_| Task::with_permit(&semaphore, || State::Pending))
+      .collect();
+    for holder in holders.iter_mut() {
+      assert_eq!(holder.poll(), None);
+    }
+    assert_eq!(semaphore.available(), 0);
+
+    let mut third: Task<_, ()> = Task::with_permit(&semaphore, || {
+      calls.fetch_add(1, Ordering::SeqCst);
+      State::Resolve(())
+    });
+    for _ in 0..10 {
+      assert_eq!(third.poll(), None);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    drop(holders.pop());
+    assert_eq!(third.poll(), Some(Ok(())));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(semaphore.available(), 1);
+  }
+
+  #[test]
+  fn can_drive_task_on_runner() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let mut runner = Runner::with_worker_count(2);
+    let ready = Arc::new(AtomicBool::new(false));
+    let dependency_ready = ready.clone();
+    let mut i = 0;
+    runner.run(Task::<(), ()>::new(move || {
+      i += 1;
+      if i < 100 {
+        return State::Pending;
+      }
+      dependency_ready.store(true, Ordering::SeqCst);
+      State::Resolve(())
+    }));
+
+    let task: Task<_, ()> = Task::new(move || {
+      if ready.load(Ordering::SeqCst) {
+        State::Resolve(42)
+      } else {
+        State::Pending
+      }
+    });
+    assert_eq!(task.drive_on(&mut runner), Some(Ok(42)));
+    runner.finish();
+  }
+
+  #[cfg(debug_assertions)]
+  #[test]
+  fn warns_when_execution_exceeds_limit() {
+    use std::sync::Mutex;
+    use std::thread;
+
+    let exceeded = Mutex::new(None);
+    let task: Task<_, ()> = Task::labeled("io", || {
+      thread::sleep(Duration::from_millis(20));
+      State::Resolve(())
+    })
+    .named("read config")
+    .time_limited_poll_with(Duration::from_millis(5), |elapsed, name, label| {
+      *exceeded.lock().unwrap() = Some((elapsed, name.map(String::from), label))
+    });
+    assert_eq!(task.name(), Some("read config"));
+    assert_eq!(task.label(), Some("io"));
+    task.wait();
+
+    let (elapsed, name, label) = exceeded.lock().unwrap().take().unwrap();
+    assert!(elapsed >= Duration::from_millis(20));
+    assert_eq!(name.as_deref(), Some("read config"));
+    assert_eq!(label, Some("io"));
+  }
+
+  #[test]
+  fn delay_jittered_resolves_within_bounds() {
+    let base = Duration::from_millis(10);
+    let jitter = Duration::from_millis(10);
+    for _ in 0..5 {
+      let start = Instant::now();
+      Task::<(), ()>::delay_jittered(base, jitter).wait();
+      let elapsed = start.elapsed();
+      assert!(elapsed >= base);
+      assert!(elapsed < base + jitter + Duration::from_millis(50));
+    }
+  }
+
+  #[test]
+  fn delay_jittered_with_rng_draws_from_given_rng() {
+    use rand::Rng;
+
+    // Always draws the lowest value in range, so no jitter is added
+    struct LowestRng;
+    impl Rng for LowestRng {
+      fn next_u32(&mut self) -> u32 {
+        0
+      }
+    }
+
+    let mut task = Task::<(), ()>::delay_jittered_with_rng(
+      Duration::from_millis(0),
+      Duration::from_secs(3600),
+      &mut LowestRng,
+    );
+    assert_eq!(task.poll(), Some(Ok(())));
+  }
+
+  #[test]
+  fn retry_with_resolves_on_later_attempt() {
+    let mut attempt = 0;
+    let task = Task::retry_with(
+      || {
+        attempt += 1;
+        if attempt == 3 {
+          Task::from(attempt)
+        } else {
+          Task::new(move || State::Reject(attempt))
+        }
+      },
+      5,
+    );
+    assert_eq!(task.wait(), Some(Ok(3)));
+  }
+
+  #[test]
+  fn retry_with_rejects_with_last_error_once_exhausted() {
+    let mut attempt = 0;
+    let task: Task<(), u32> = Task::retry_with(
+      || {
+        attempt += 1;
+        Task::new(move || State::Reject(attempt))
+      },
+      3,
+    );
+    assert_eq!(task.wait(), Some(Err(3)));
+  }
+
+  #[test]
+  fn can_create_task_from_result() {
+    assert_eq!(Task::from_result(Ok::<u32, &str>(42)).wait(), Some(Ok(42)));
+    assert_eq!(Task::from_result(Err::<u32, &str>("failed")).wait(), Some(Err("failed")));
+  }
+
+  #[test]
+  fn repeat_collects_each_run_in_order() {
+    let task = Task::repeat(|i| resolve_after(2, i as u32 * 10), 3);
+    assert_eq!(task.wait(), Some(Ok(vec![0, 10, 20])));
+  }
+
+  #[test]
+  fn repeat_rejects_on_first_failure() {
+    let mut made = 0;
+    let task = Task::repeat(
+      |i| {
+        made += 1;
+        if i == 1 {
+          Task::new(|| State::Reject("failed"))
+        } else {
+          resolve_after(1, i as u32)
+        }
+      },
+      3,
+    );
+    assert_eq!(task.wait(), Some(Err("failed")));
+    assert_eq!(made, 2);
+  }
+
+  #[test]
+  fn debug_trace_logs_when_enabled() {
+    use std::process::Command;
+
+    if env::var_os("TASK_KIT_TRACE").is_some() {
+      let task: Task<_, ()> = Task::from(1).debug_trace("traced-task");
+      task.wait();
+      return;
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+      .args([
+        "--exact",
+        "task::task::tests::debug_trace_logs_when_enabled",
+        "--nocapture",
+      ])
+      .env("TASK_KIT_TRACE", "1")
+      .output()
+      .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("task_kit: [traced-task] poll 1: State::Resolve"));
+  }
+
+  #[test]
+  fn can_stream_pending_values() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let count = AtomicUsize::new(0);
+    let task: Task<_, ()> = Task::new(|| {
+      let count = count.fetch_add(1, Ordering::SeqCst) + 1;
+      if count == 5 {
+        State::Resolve(count)
+      } else {
+        State::Pending
+      }
+    });
+    let (task, receiver) = task.stream_pending(|| Some(count.load(Ordering::SeqCst)));
+    assert_eq!(task.wait(), Some(Ok(5)));
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+  }
+
+  #[test]
+  fn can_materialize_and_dematerialize_rejection() {
+    let task: Task<u32, &str> = Task::new(|| State::Reject("oops"));
+    let materialized = task.materialize().map(|state| {
+      assert_eq!(state, State::Reject("oops"));
+      state
+    });
+    assert_eq!(materialized.dematerialize().wait(), Some(Err("oops")));
+  }
+
   #[test]
   fn can_use_done() {
     let task: Task<_, ()> = Task::new(|| State::Resolve(1)).done(|val| assert_eq!(val, 1));
     task.wait();
   }
+
+  #[test]
+  fn can_join_tasks_with_erased_errors() {
+    use std::fmt::Error as FmtError;
+    use std::num::ParseIntError;
+
+    let a: Task<u32, ParseIntError> = Task::new(|| State::Resolve(1));
+    let b: Task<u32, FmtError> = Task::new(|| State::Reject(FmtError));
+    let result = a.erase_error().join(b.erase_error()).wait().unwrap();
+    assert_eq!(result.unwrap_err().to_string(), FmtError.to_string());
+  }
+
+  #[test]
+  fn can_join_tasks_with_bitand() {
+    let a: Task<_, ()> = Task::from(1);
+    let b: Task<_, ()> = Task::from("two");
+    assert_eq!((a & b).wait(), Some(Ok((1, "two"))));
+  }
+
+  #[test]
+  fn can_race_tasks_with_bitor() {
+    let a: Task<u32, &str> = Task::new(|| State::Reject("a failed"));
+    let b: Task<u32, &str> = Task::from(2);
+    let c: Task<u32, &str> = Task::new(|| State::Pending);
+    assert_eq!((a | b | c).wait(), Some(Ok(2)));
+
+    let a: Task<u32, &str> = Task::new(|| State::Reject("a failed"));
+    let b: Task<u32, &str> = Task::new(|| State::Reject("b failed"));
+    assert_eq!((a | b).wait(), Some(Err("b failed")));
+  }
+
+  #[test]
+  fn can_send_result_to_channel() {
+    let mut runner = Runner::with_worker_count(1);
+    let (sender, receiver) = channel();
+    let task: Task<_, ()> = Task::from(42);
+    runner.run(task.with_result_sender(sender));
+    assert_eq!(receiver.recv(), Ok(Ok(42)));
+    runner.finish();
+  }
+
+  #[test]
+  fn can_delay_first_execution() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    let calls = AtomicUsize::new(0);
+    let mut task: Task<_, ()> = Task::new(|| {
+      State::Resolve(calls.fetch_add(1, Ordering::SeqCst) + 1)
+    })
+    .after(Duration::from_millis(20));
+
+    assert_eq!(task.poll(), None);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(task.poll(), Some(Ok(1)));
+  }
+
+  #[test]
+  fn can_flatten_result() {
+    let task: Task<Result<u32, &str>, &str> = Task::from(Ok(1));
+    assert_eq!(task.flatten_result().wait(), Some(Ok(1)));
+    let task: Task<Result<u32, &str>, &str> = Task::from(Err("oops"));
+    assert_eq!(task.flatten_result().wait(), Some(Err("oops")));
+  }
+
+  #[test]
+  fn can_create_task_from_boxed_closure() {
+    let mut i = 0;
+    let closure: Box<dyn FnMut() -> State<u32, ()> + Send> = Box::new(move || {
+      i += 1;
+      if i == 3 {
+        State::Resolve(i)
+      } else {
+        State::Pending
+      }
+    });
+    assert_eq!(Task::from_boxed(closure).wait(), Some(Ok(3)));
+  }
+
+  #[test]
+  fn can_limit_poll_rate_across_tasks() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+
+    let polls = Arc::new(AtomicUsize::new(0));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let limiter = RateLimiter::new(10);
+    let mut runner = Runner::with_worker_count(2);
+    for _ in 0..10 {
+      let polls = polls.clone();
+      let stopped = stopped.clone();
+      let task = Task::<(), ()>::new(move || {
+        polls.fetch_add(1, Ordering::SeqCst);
+        if stopped.load(Ordering::SeqCst) {
+          State::Resolve(())
+        } else {
+          State::Pending
+        }
+      });
+      runner.run(task.rate_limited(&limiter));
+    }
+
+    // At ten tokens a second, the tasks are polled no more than once on
+    // starting and once for each tenth of a second after
+    thread::sleep(Duration::from_secs(1));
+    let polled = polls.load(Ordering::SeqCst);
+    stopped.store(true, Ordering::SeqCst);
+    runner.finish();
+
+    assert!(polled <= 11, "polled {} times", polled);
+  }
+
+  #[test]
+  fn delay_defers_task_from_first_execution() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    let ran = AtomicBool::new(false);
+    let mut task: Task<_, ()> = Task::with(|| ran.store(true, Ordering::SeqCst))
+      .delay(Duration::from_millis(30));
+    thread::sleep(Duration::from_millis(40));
+    assert_eq!(task.poll(), None);
+    assert!(!ran.load(Ordering::SeqCst));
+
+    let started_at = Instant::now();
+    assert_eq!(task.wait(), Some(Ok(())));
+    assert!(started_at.elapsed() >= Duration::from_millis(20));
+    assert!(ran.load(Ordering::SeqCst));
+  }
+
+  #[test]
+  fn timeout_rejects_task_that_never_settles() {
+    use std::thread;
+
+    let mut task: Task<(), TimeoutError> = Task::new(|| State::Pending)
+      .timeout(Duration::from_millis(50));
+    thread::sleep(Duration::from_millis(60));
+    assert_eq!(task.poll(), None);
+
+    let started_at = Instant::now();
+    assert_eq!(task.wait(), Some(Err(TimeoutError)));
+    assert!(started_at.elapsed() >= Duration::from_millis(50));
+  }
+
+  #[test]
+  fn timeout_stops_polling_task_once_fired() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+
+    let polls = Arc::new(AtomicUsize::new(0));
+    let dropped = Arc::new(AtomicBool::new(false));
+    let task_polls = polls.clone();
+    let task_dropped = dropped.clone();
+    let mut task: Task<(), TimeoutError> = Task::new(move || {
+      task_polls.fetch_add(1, Ordering::SeqCst);
+      State::Pending
+    })
+    .on_drop(move || task_dropped.store(true, Ordering::SeqCst))
+    .timeout(Duration::from_millis(20));
+
+    while task.poll().is_none() {
+      thread::sleep(Duration::from_millis(1));
+    }
+    assert!(dropped.load(Ordering::SeqCst));
+
+    let polls_at_timeout = polls.load(Ordering::SeqCst);
+    assert_eq!(task.poll(), None);
+    assert_eq!(polls.load(Ordering::SeqCst), polls_at_timeout);
+  }
+
+  #[test]
+  fn idle_timeout_resets_on_progress() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    let progressing = AtomicBool::new(true);
+    let mut task: Task<(), TimeoutError> = Task::new(|| State::Pending)
+      .idle_timeout(Duration::from_millis(20), || progressing.load(Ordering::SeqCst));
+
+    for _ in 0..4 {
+      thread::sleep(Duration::from_millis(10));
+      assert_eq!(task.poll(), None);
+    }
+    progressing.store(false, Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(task.poll(), Some(Err(TimeoutError)));
+  }
+
+  #[test]
+  fn can_time_task() {
+    let delay = Task::delay_jittered(Duration::from_millis(20), Duration::from_millis(0));
+    let task: Task<_, ()> = delay.map(|_| 1).timed();
+    let (value, elapsed) = task.wait().unwrap().unwrap();
+    assert_eq!(value, 1);
+    assert!(elapsed >= Duration::from_millis(20));
+  }
+
+  #[test]
+  fn can_monitor_task_from_another_thread() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use super::super::StateKind;
+
+    let mut runner = Runner::with_worker_count(1);
+    let released = Arc::new(AtomicBool::new(false));
+    let task_released = released.clone();
+    let (task, watch) = Task::<(), ()>::new(move || {
+      if task_released.load(Ordering::SeqCst) {
+        State::Resolve(())
+      } else {
+        State::Pending
+      }
+    })
+    .monitored();
+
+    assert_eq!(watch.current(), StateKind::Pending);
+    let handle = runner.spawn(task);
+    assert_eq!(watch.current(), StateKind::Pending);
+    released.store(true, Ordering::SeqCst);
+    assert_eq!(handle.wait(), Some(Ok(())));
+    assert_eq!(watch.current(), StateKind::Resolved);
+    runner.finish();
+  }
+
+  #[test]
+  fn context_is_attached_to_errors() {
+    let task = Task::<u32, &str>::from(2)
+      .context("load")
+      .and_then(|v| {
+        Task::new(move || if v > 1 { State::Reject("too large") } else { State::Resolve(v) })
+          .context("validate")
+      })
+      .and_then(|v| Task::from(v * 2).context("double"));
+    assert_eq!(task.wait(), Some(Err(("validate", "too large"))));
+  }
+
+  #[test]
+  fn can_recover_with_task() {
+    let task: Task<u32, &str> = Task::new(|| State::Reject("oops"));
+    let task = task.recover_with(|e| {
+      assert_eq!(e, "oops");
+      let mut polls = 0;
+      Task::<_, ()>::new(move || {
+        polls += 1;
+        if polls == 3 {
+          State::Resolve(polls)
+        } else {
+          State::Pending
+        }
+      })
+    });
+    assert_eq!(task.wait(), Some(Ok(3)));
+  }
+
+  #[test]
+  fn or_else_forwards_to_recover_with() {
+    let task: Task<u32, &str> = Task::new(|| State::Reject("oops"));
+    let task = task.or_else(|e| Task::<_, ()>::from(e.len() as u32));
+    assert_eq!(task.wait(), Some(Ok(4)));
+
+    let task: Task<u32, &str> = Task::from(1);
+    let task = task.or_else(|_| Task::<_, ()>::from(2));
+    assert_eq!(task.wait(), Some(Ok(1)));
+  }
+
+  #[test]
+  fn can_resolve_all_tasks_in_order() {
+    let tasks = (1..4).map(|n| {
+      let mut polls = 0;
+      Task::<_, ()>::new(move || {
+        polls += 1;
+        if polls == 4 - n {
+          State::Resolve(n)
+        } else {
+          State::Pending
+        }
+      })
+    });
+    assert_eq!(Task::all(tasks).wait(), Some(Ok(vec![1, 2, 3])));
+  }
+
+  #[test]
+  fn can_resolve_all_of_no_tasks() {
+    let mut task = Task::all(Vec::<Task<u32, ()>>::new());
+    assert_eq!(task.poll(), Some(Ok(vec![])));
+  }
+
+  #[test]
+  fn all_rejects_with_first_error() {
+    let tasks = vec![
+      Task::from(1),
+      Task::new(|| State::Pending),
+      Task::new(|| State::Reject("first")),
+      Task::new(|| State::Reject("second")),
+    ];
+    assert_eq!(Task::all(tasks).wait(), Some(Err("first")));
+  }
+
+  fn resolve_after<'a>(polls: u32, value: u32) -> Task<'a, u32, &'static str> {
+    let mut count = 0;
+    Task::new(move || {
+      count += 1;
+      if count == polls {
+        State::Resolve(value)
+      } else {
+        State::Pending
+      }
+    })
+  }
+
+  #[test]
+  fn sequence_runs_tasks_in_order() {
+    use std::sync::Mutex;
+
+    let started = Mutex::new(Vec::new());
+    let mut a_polls = 0;
+    let a = Task::new(|| {
+      started.lock().unwrap().push("a");
+      a_polls += 1;
+      if a_polls == 3 {
+        State::Resolve(1)
+      } else {
+        State::Pending
+      }
+    });
+    let b = Task::new(|| {
+      started.lock().unwrap().push("b");
+      State::Resolve("two")
+    });
+    let c: Task<_, ()> = Task::new(|| {
+      started.lock().unwrap().push("c");
+      State::Resolve(3.0)
+    });
+
+    let result: Option<Result<(u32, &str, f64), ()>> = Task::sequence3(a, b, c).wait();
+    assert_eq!(result, Some(Ok((1, "two", 3.0))));
+    assert_eq!(*started.lock().unwrap(), vec!["a", "a", "a", "b", "c"]);
+  }
+
+  #[test]
+  fn sequence_does_not_run_tasks_after_rejection() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let b_ran = AtomicBool::new(false);
+    let a: Task<u32, &str> = Task::new(|| State::Reject("failed"));
+    let b = Task::new(|| {
+      b_ran.store(true, Ordering::SeqCst);
+      State::Resolve(2)
+    });
+    assert_eq!(Task::sequence2(a, b).wait(), Some(Err("failed")));
+    assert!(!b_ran.load(Ordering::SeqCst));
+  }
+
+  #[test]
+  fn flatten_resolves_with_inner_task() {
+    let task = resolve_after(2, 20).map(|v| resolve_after(3, v + 1));
+    assert_eq!(task.flatten().wait(), Some(Ok(21)));
+  }
+
+  #[test]
+  fn flatten_rejects_with_inner_task() {
+    let task = resolve_after(2, 20).map(|_| Task::<u32, _>::new(|| State::Reject("inner")));
+    assert_eq!(task.flatten().wait(), Some(Err("inner")));
+  }
+
+  #[test]
+  fn can_chain_tasks_with_and_then() {
+    let task = resolve_after(2, 1)
+      .and_then(|v| resolve_after(2, v + 1))
+      .and_then(|v| resolve_after(3, v * 10))
+      .and_then(|v| resolve_after(1, v + 2));
+    assert_eq!(task.wait(), Some(Ok(22)));
+  }
+
+  #[test]
+  fn and_then_passes_through_rejection() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let last_ran = AtomicBool::new(false);
+    let task = resolve_after(2, 1)
+      .and_then(|_| Task::<u32, _>::new(|| State::Reject("middle")))
+      .and_then(|v| {
+        last_ran.store(true, Ordering::SeqCst);
+        resolve_after(1, v)
+      });
+    assert_eq!(task.wait(), Some(Err("middle")));
+    assert!(!last_ran.load(Ordering::SeqCst));
+  }
+
+  #[test]
+  fn race_resolves_with_fastest_task() {
+    let tasks = vec![resolve_after(5, 1), resolve_after(2, 2), resolve_after(2, 3)];
+    assert_eq!(Task::race(tasks).wait(), Some(Ok(2)));
+  }
+
+  #[test]
+  fn race_rejects_if_first_to_settle_rejects() {
+    let tasks = vec![resolve_after(3, 1), Task::new(|| State::Reject("fast failure"))];
+    assert_eq!(Task::race(tasks).wait(), Some(Err("fast failure")));
+  }
+
+  #[test]
+  fn finally_is_called_once_task_settles() {
+    let mut settled_with = Vec::new();
+    {
+      let resolved = Task::<_, &str>::from(1).finally(|s| settled_with.push(s.to_string()));
+      assert_eq!(resolved.wait(), Some(Ok(1)));
+    }
+    {
+      let rejected = Task::<u32, _>::new(|| State::Reject("failed"))
+        .finally(|s| settled_with.push(s.to_string()));
+      assert_eq!(rejected.wait(), Some(Err("failed")));
+    }
+    assert_eq!(settled_with, vec!["Resolve(1)", "Reject(failed)"]);
+  }
+
+  #[test]
+  fn finally_is_not_called_while_pending() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = AtomicUsize::new(0);
+    let mut task = resolve_after(3, 1).finally(|_| {
+      calls.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(task.poll(), None);
+    assert_eq!(task.poll(), None);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    assert_eq!(task.poll(), Some(Ok(1)));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn inspect_sees_resolved_value_once() {
+    let mut seen = Vec::new();
+    let task = resolve_after(3, 7)
+      .inspect(|v| seen.push(*v))
+      .inspect_err(|_| panic!("task did not reject"))
+      .map(|v| v * 2);
+    assert_eq!(task.wait(), Some(Ok(14)));
+    assert_eq!(seen, vec![7]);
+  }
+
+  #[test]
+  fn inspect_err_sees_rejected_error_once() {
+    let mut seen = Vec::new();
+    let task = Task::<u32, _>::new(|| State::Reject("failed"))
+      .inspect(|_| panic!("task did not resolve"))
+      .inspect_err(|e| seen.push(*e));
+    assert_eq!(task.wait(), Some(Err("failed")));
+    assert_eq!(seen, vec!["failed"]);
+  }
+
+  #[test]
+  fn race_calls_on_drop_of_losing_tasks_only() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let winner_drops = AtomicUsize::new(0);
+    let loser_drops = AtomicUsize::new(0);
+    {
+      let winner = resolve_after(2, 1).on_drop(|| {
+        winner_drops.fetch_add(1, Ordering::SeqCst);
+      });
+      let loser = resolve_after(5, 2).on_drop(|| {
+        loser_drops.fetch_add(1, Ordering::SeqCst);
+      });
+      assert_eq!(Task::race(vec![loser, winner]).wait(), Some(Ok(1)));
+    }
+    assert_eq!(winner_drops.load(Ordering::SeqCst), 0);
+    assert_eq!(loser_drops.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn race_of_no_tasks_stays_pending() {
+    let mut task = Task::race(Vec::<Task<u32, ()>>::new());
+    assert_eq!(task.poll(), None);
+    assert_eq!(task.poll(), None);
+  }
 }
@@ -0,0 +1,18 @@
+use std::error::Error;
+use std::fmt;
+
+/// The error a task rejects with when it times out.
+///
+/// Tasks with timeouts require their error type to implement
+/// `From<TimeoutError>`, so that the timeout can be reported alongside the
+/// task's own errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutError;
+
+impl fmt::Display for TimeoutError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "task timed out")
+  }
+}
+
+impl Error for TimeoutError {}
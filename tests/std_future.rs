@@ -0,0 +1,23 @@
+extern crate task_kit;
+extern crate tokio;
+
+use task_kit::prelude::*;
+
+#[tokio::test]
+async fn can_await_task() {
+  let mut polls = 0;
+  let task: Task<_, ()> = Task::new(move || {
+    polls += 1;
+    if polls < 3 {
+      return Pending;
+    }
+    Resolve(polls)
+  });
+  assert_eq!(task.await, Ok(3));
+}
+
+#[tokio::test]
+async fn can_await_rejected_task() {
+  let task: Task<(), _> = Task::new(|| Reject("oops"));
+  assert_eq!(task.await, Err("oops"));
+}
@@ -0,0 +1,8 @@
+extern crate trybuild;
+
+#[test]
+fn ui() {
+  let cases = trybuild::TestCases::new();
+  cases.compile_fail("tests/ui/fail/*.rs");
+  cases.pass("tests/ui/pass/*.rs");
+}
@@ -0,0 +1,12 @@
+extern crate task_kit;
+
+use std::rc::Rc;
+use task_kit::prelude::*;
+
+fn main() {
+  let mut runner = Runner::new();
+  let shared = Rc::new(1);
+  let task: Task<u32, ()> = Task::new(move || Resolve(*shared));
+  runner.run(task);
+  runner.finish();
+}
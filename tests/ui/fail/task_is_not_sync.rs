@@ -0,0 +1,9 @@
+extern crate task_kit;
+
+use task_kit::prelude::*;
+
+fn assert_sync<T: Sync>() {}
+
+fn main() {
+  assert_sync::<Task<u32, ()>>();
+}
@@ -0,0 +1,17 @@
+extern crate task_kit;
+
+use std::sync::Arc;
+use task_kit::prelude::*;
+
+fn assert_send<T: Send>() {}
+
+fn main() {
+  assert_send::<Task<u32, ()>>();
+
+  let mut runner = Runner::new();
+  let shared = Arc::new(1);
+  let task: Task<u32, ()> = Task::new(move || Resolve(*shared));
+  let handle = runner.spawn(task);
+  assert_eq!(handle.wait(), Some(Ok(1)));
+  runner.finish();
+}